@@ -0,0 +1,106 @@
+//! Builds the guest programs with the Pico toolchain and embeds them into the binary.
+//!
+//! The guests are built with `cargo pico build` under the toolchain pinned in
+//! `verification-app/rust-toolchain.toml`, so the same sources always give the same ELF
+//! and program hash. To embed prebuilt ELFs instead, for example in CI without the Pico
+//! CLI, point `PICO_EMBED_GUEST_ELF` and `PICO_EMBED_BATCH_ELF` at them.
+
+use sha2::{Digest, Sha256};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where `cargo pico build` leaves the ELF, relative to the guest crate
+const PICO_ELF: &str = "elf/riscv32im-pico-zkvm-elf";
+
+struct Guest {
+    /// Crate directory under `verification-app`
+    dir: &'static str,
+    /// Variable pointing at a prebuilt ELF to embed instead
+    prebuilt_var: &'static str,
+    /// File name in `OUT_DIR` and the variables its program hash and crate version are exported as
+    out_file: &'static str,
+    hash_var: &'static str,
+    version_var: &'static str,
+}
+
+const GUESTS: [Guest; 2] = [
+    Guest {
+        dir: "app",
+        prebuilt_var: "PICO_EMBED_GUEST_ELF",
+        out_file: "material-verification.elf",
+        hash_var: "GUEST_PROGRAM_HASH",
+        version_var: "GUEST_VERSION",
+    },
+    Guest {
+        dir: "batch",
+        prebuilt_var: "PICO_EMBED_BATCH_ELF",
+        out_file: "material-verification-batch.elf",
+        hash_var: "BATCH_PROGRAM_HASH",
+        version_var: "BATCH_GUEST_VERSION",
+    },
+];
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let guests_dir = manifest_dir.join("../verification-app");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    // Guest sources shared by both programs
+    println!("cargo:rerun-if-changed={}", guests_dir.join("lib").display());
+    println!("cargo:rerun-if-changed={}", guests_dir.join("rust-toolchain.toml").display());
+
+    for guest in &GUESTS {
+        let crate_dir = guests_dir.join(guest.dir);
+        let manifest = crate_dir.join("Cargo.toml");
+        println!("cargo:rerun-if-changed={}", manifest.display());
+        println!("cargo:rustc-env={}={}", guest.version_var, crate_version(&manifest));
+
+        println!("cargo:rerun-if-env-changed={}", guest.prebuilt_var);
+        let elf_path = match env::var_os(guest.prebuilt_var) {
+            Some(path) => PathBuf::from(path),
+            None => {
+                println!("cargo:rerun-if-changed={}", crate_dir.join("src").display());
+                build_guest(&crate_dir, &out_dir.join("guest-target"), guest.prebuilt_var)
+            }
+        };
+
+        let elf = std::fs::read(&elf_path)
+            .unwrap_or_else(|err| panic!("Failed to read guest ELF {}: {}", elf_path.display(), err));
+        std::fs::write(out_dir.join(guest.out_file), &elf).unwrap();
+        println!("cargo:rustc-env={}={}", guest.hash_var, hex::encode(Sha256::digest(&elf)));
+    }
+}
+
+fn crate_version(manifest: &Path) -> String {
+    let content = std::fs::read_to_string(manifest)
+        .unwrap_or_else(|err| panic!("Failed to read {}: {}", manifest.display(), err));
+    let manifest: toml::Table = content.parse()
+        .unwrap_or_else(|err| panic!("Invalid manifest {}: {}", manifest.display(), err));
+    manifest["package"]["version"].as_str().expect("guest crate has a version").to_string()
+}
+
+/// Run `cargo pico build` in a guest crate and return the ELF it produced
+fn build_guest(crate_dir: &Path, target_dir: &Path, prebuilt_var: &str) -> PathBuf {
+    // Through the rustup proxy rather than $CARGO, so the guest's pinned toolchain is used
+    let mut command = Command::new("cargo");
+    command.args(["pico", "build"]).current_dir(crate_dir);
+
+    // Settings of this build must not leak into the guest's, which has its own toolchain and target
+    for (key, _) in env::vars_os() {
+        let key = key.to_string_lossy();
+        if (key.starts_with("CARGO_") && key != "CARGO_HOME") || key.starts_with("RUSTC") || key == "RUSTUP_TOOLCHAIN" || key == "RUSTFLAGS" {
+            command.env_remove(key.as_ref());
+        }
+    }
+    command.env("CARGO_TARGET_DIR", target_dir);
+
+    let status = command.status().unwrap_or_else(|err| {
+        panic!("Failed to run `cargo pico build` in {}: {}; install the Pico CLI or set {}", crate_dir.display(), err, prebuilt_var)
+    });
+    if !status.success() {
+        panic!("`cargo pico build` failed in {} ({})", crate_dir.display(), status);
+    }
+
+    crate_dir.join(PICO_ELF)
+}
@@ -1,72 +1,200 @@
+use crate::audit::{AuditLog, Outcome};
+use crate::auth::{Action, Authenticator, Principal};
+use crate::batch;
+use crate::bundle::{self, Issuer};
 use crate::circuit;
-use crate::types::{MaterialCertification, VerificationResult, PropertyResult, 
-  ComplianceSummary, ZkpInfo, MaterialInfo, BatchInfo, PropertiesInfo, 
-  PropertyValue, Threshold, ComplianceInfo, VerificationInfo};
-use anyhow::Result;
+use crate::config::ServerConfig;
+use crate::evm;
+use crate::metrics;
+use crate::registry;
+use crate::schema::{SchemaType, SchemaValidator};
+use crate::store::ProofQuery;
+use crate::tenant::{Tenant, Tenants};
+use crate::tls;
+use crate::legacy::{self, LegacyProofBundle};
+use crate::error::{self, ApiError};
+use crate::types::{self, MaterialCertification, ProofId, ProofRecord, ProvingMode};
+use crate::validation;
+use anyhow::{Context, Result};
+use futures_util::TryStreamExt;
 use std::convert::Infallible;
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
 use std::sync::Arc;
+use tokio::net::{TcpListener, UnixListener};
+use tokio::sync::Semaphore;
+use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
 use warp::{Filter, Rejection, Reply};
+use warp::http::Method;
+use warp::hyper::body::{Buf, Bytes};
+use warp::multipart::FormData;
 use warp::reply::json;
-use warp::cors::Cors;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 // Request/Response types
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct GenerateProofRequest {
     pub certification: MaterialCertification,
+    #[serde(default)]
+    pub mode: ProvingMode,
+    /// Prove again even if an identical proof already exists
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GenerateBatchProofRequest {
+    pub certifications: Vec<MaterialCertification>,
+    #[serde(default)]
+    pub mode: ProvingMode,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PreflightRequest {
+    pub certification: MaterialCertification,
+    #[serde(default)]
+    pub mode: ProvingMode,
 }
 
 #[derive(Debug, Serialize)]
 pub struct GenerateProofResponse {
-    pub proof_id: String,
+    pub proof_id: ProofId,
     pub status: String,
     pub message: String,
+    pub reused: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct VerifyProofRequest {
-    pub proof_id: String,
+    pub proof_id: ProofId,
     pub certification_id: String,
 }
 
+/// Optional pins for `POST /api/verify-bundle`, given in the query string
+#[derive(Debug, Deserialize)]
+pub struct VerifyBundleQuery {
+    /// Issuer public key (hex) the bundle must be signed with; it must also be one the server trusts
+    pub issuer_key: Option<String>,
+    /// SHA-256 of the guest program the proof must come from
+    pub program_hash: Option<String>,
+}
+
+/// Largest proof bundle accepted for verification
+const MAX_BUNDLE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Largest certification accepted for validation
+const MAX_CERTIFICATION_SIZE: u64 = 1024 * 1024;
+
 // API state
-#[derive(Clone)]
 pub struct ApiState {
-    pub output_dir: PathBuf,
+    /// Each organisation's proof store and working directory
+    pub tenants: Tenants,
+    /// Identifies callers and their roles
+    pub auth: Authenticator,
+    /// Where every access decision is recorded
+    pub audit: AuditLog,
+    /// Signs downloadable proof bundles
+    pub issuer: Issuer,
+    /// Public keys of other servers whose bundles are accepted, besides `issuer`'s own
+    pub trusted_issuers: Vec<String>,
+    /// One permit per proof that may be generated at the same time, held until proving ends even
+    /// if the request is abandoned
+    pub proof_slots: Arc<Semaphore>,
 }
 
 // Main API function to start the server
-pub async fn start_api(port: u16, output_dir: PathBuf) -> Result<()> {
-    let state = Arc::new(ApiState { output_dir });
+pub async fn start_api(server: &ServerConfig, state: ApiState) -> Result<()> {
+    let state = Arc::new(state);
     
     // Define routes
     let api = generate_proof_route(Arc::clone(&state))
-        .or(verify_proof_route(Arc::clone(&state)));
+        .or(verify_proof_route(Arc::clone(&state)))
+        .or(generate_batch_proof_route(Arc::clone(&state)))
+        .or(batch_inclusion_route(Arc::clone(&state)))
+        .or(preflight_route(Arc::clone(&state)))
+        .or(validate_route(Arc::clone(&state)))
+        .or(verify_legacy_proof_route(Arc::clone(&state)))
+        .or(verify_bundle_route(Arc::clone(&state)))
+        .or(evm_export_route(Arc::clone(&state)))
+        .or(get_proof_route(Arc::clone(&state)))
+        .or(bundle_route(Arc::clone(&state)))
+        .or(list_proofs_route(Arc::clone(&state)))
+        .or(guest_versions_route(Arc::clone(&state)))
+        .or(schema_route(Arc::clone(&state)))
+        .or(delete_proof_route(Arc::clone(&state)))
+        .or(metrics_route(Arc::clone(&state)));
 
-    let cors = warp::cors()
-        .allow_any_origin() // TODO definitely don't deploy this
-        .allow_methods(vec!["POST", "GET", "OPTIONS"])
-        .allow_headers(vec!["Content-Type"])
-        .allow_credentials(true);
+    let cors = &server.cors;
+    let mut cors_filter = if cors.origins.is_empty() {
+        tracing::warn!("Allowing API calls from any origin; set server.cors.origins to restrict them");
+        warp::cors().allow_any_origin()
+    } else {
+        warp::cors().allow_origins(cors.origins.iter().map(String::as_str))
+    };
+    cors_filter = cors_filter
+        .allow_methods(cors.methods.iter().map(String::as_str))
+        .allow_headers(cors.headers.iter().map(String::as_str))
+        .allow_credentials(cors.allow_credentials);
+    if let Some(seconds) = cors.max_age {
+        cors_filter = cors_filter.max_age(std::time::Duration::from_secs(seconds));
+    }
+    
+    // Apply CORS to routes and render every failure as problem details
+    let routes = api.recover(error::handle_rejection).with(cors_filter);
+    let service = warp::serve(routes);
     
-    // Apply CORS to routes
-    let routes = api.with(cors);
+    if let Some(addr) = server.metrics_address {
+        serve_metrics(addr).await?;
+    }
     
     // Start the server
-    tracing::info!("Starting API server on port {}", port);
-    warp::serve(routes).run(([0, 0, 0, 0], port)).await;
+    if let Some(path) = &server.unix_socket {
+        let listener = bind_unix_socket(path)?;
+        tracing::info!("Starting API server on Unix socket {:?}", path);
+        service.run_incoming(UnixListenerStream::new(listener)).await;
+        return Ok(());
+    }
+
+    let addr = SocketAddr::new(server.address, server.port);
+    let listener = TcpListener::bind(addr).await
+        .with_context(|| format!("Failed to listen on {}", addr))?;
+    match &server.tls {
+        Some(tls_config) => {
+            let tls_listener = tls::TlsListener::new(tls_config)?;
+            tracing::info!("Starting API server on https://{}", addr);
+            service.run_incoming(tls_listener.incoming(listener)).await;
+        }
+        None => {
+            tracing::info!("Starting API server on http://{}", addr);
+            service.run_incoming(TcpListenerStream::new(listener)).await;
+        }
+    }
     
     Ok(())
 }
 
+/// Bind a Unix socket, replacing one left behind by an earlier run
+fn bind_unix_socket(path: &Path) -> Result<UnixListener> {
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            anyhow::bail!("{:?} exists and is not a socket", path);
+        }
+        std::fs::remove_file(path).with_context(|| format!("Failed to remove stale socket {:?}", path))?;
+    }
+    UnixListener::bind(path).with_context(|| format!("Failed to listen on {:?}", path))
+}
+
 // Route for generating proofs
 fn generate_proof_route(
     state: Arc<ApiState>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     warp::path!("api" / "generate-proof")
         .and(warp::post())
-        .and(warp::body::json())
+        .and(authorize(Arc::clone(&state), Action::Prove))
+        .and(checked_json())
         .and(with_state(state))
         .and_then(handle_generate_proof)
 }
@@ -77,11 +205,186 @@ fn verify_proof_route(
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     warp::path!("api" / "verify-proof")
         .and(warp::post())
+        .and(authorize(Arc::clone(&state), Action::Verify))
         .and(warp::body::json())
         .and(with_state(state))
         .and_then(handle_verify_proof)
 }
 
+// Route for proving many certifications in one batch
+fn generate_batch_proof_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "generate-batch-proof")
+        .and(warp::post())
+        .and(authorize(Arc::clone(&state), Action::Prove))
+        .and(checked_json())
+        .and(with_state(state))
+        .and_then(handle_generate_batch_proof)
+}
+
+// Route for checking a single certificate against a batch proof
+fn batch_inclusion_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "batches" / String / "certificates" / String)
+        .and(warp::get())
+        .and(authorize(Arc::clone(&state), Action::Verify))
+        .and(with_state(state))
+        .and_then(handle_batch_inclusion)
+}
+
+// Route for emulation-only preflight checks
+fn preflight_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "preflight")
+        .and(warp::post())
+        .and(authorize(Arc::clone(&state), Action::Prove))
+        .and(checked_json())
+        .and(with_state(state))
+        .and_then(handle_preflight)
+}
+
+// Route for checking a certification field by field without proving it
+fn validate_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "validate")
+        .and(warp::post())
+        .and(require(state, Action::Verify))
+        .and(warp::body::content_length_limit(MAX_CERTIFICATION_SIZE))
+        .and(warp::body::bytes())
+        .and_then(handle_validate)
+}
+
+// Route for verifying proofs from the legacy snarkjs pipeline
+fn verify_legacy_proof_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "verify-legacy-proof")
+        .and(warp::post())
+        .and(require(state, Action::Verify))
+        .and(warp::body::json())
+        .and_then(handle_verify_legacy_proof)
+}
+
+// Route for verifying an uploaded proof bundle, without looking anything up server-side. The
+// bundle is either the raw request body (any content type) or the `bundle` part of a
+// multipart/form-data upload.
+fn verify_bundle_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "verify-bundle")
+        .and(warp::post())
+        .and(require(Arc::clone(&state), Action::Verify))
+        .and(warp::query::<VerifyBundleQuery>())
+        .and(bundle_upload())
+        .and(with_state(state))
+        .and_then(handle_verify_bundle)
+}
+
+// Route for exporting the Solidity verifier and calldata of an evm proof
+fn evm_export_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "proofs" / String / "solidity")
+        .and(warp::get())
+        .and(authorize(Arc::clone(&state), Action::Verify))
+        .and(with_state(state))
+        .and_then(handle_evm_export)
+}
+
+// Route for fetching a stored proof record
+fn get_proof_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "proofs" / String)
+        .and(warp::get())
+        .and(authorize(Arc::clone(&state), Action::Verify))
+        .and(with_state(state))
+        .and_then(handle_get_proof)
+}
+
+// Route for downloading a signed bundle for offline verification
+fn bundle_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "proofs" / String / "bundle")
+        .and(warp::get())
+        .and(authorize(Arc::clone(&state), Action::Sign))
+        .and(with_state(state))
+        .and_then(handle_bundle)
+}
+
+// Route for listing and searching stored proofs
+fn list_proofs_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "proofs")
+        .and(warp::get())
+        .and(authorize(Arc::clone(&state), Action::Verify))
+        .and(warp::query::<ProofQuery>())
+        .and(with_state(state))
+        .and_then(handle_list_proofs)
+}
+
+// Route for listing the guest versions whose proofs are accepted
+fn guest_versions_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "guest-versions")
+        .and(warp::get())
+        .and(require(state, Action::Verify))
+        .and_then(handle_guest_versions)
+}
+
+// Route for the published JSON Schemas
+fn schema_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "schema" / String)
+        .and(warp::get())
+        .and(require(state, Action::Verify))
+        .and_then(handle_schema)
+}
+
+// Route for deleting a stored proof
+fn delete_proof_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "proofs" / String)
+        .and(warp::delete())
+        .and(authorize(Arc::clone(&state), Action::Manage))
+        .and(with_state(state))
+        .and_then(handle_delete_proof)
+}
+
+// Route for Prometheus scraping; the metrics reveal request volumes and failures, so only admins
+// may read them on the API
+fn metrics_route(
+    state: Arc<ApiState>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("metrics")
+        .and(warp::get())
+        .and(require(state, Action::Manage))
+        .map(render_metrics)
+}
+
+fn render_metrics() -> impl Reply {
+    warp::reply::with_header(metrics::render(), "Content-Type", "text/plain; version=0.0.4")
+}
+
+/// Serve `/metrics` alone, without authentication, on an address kept for the scraper
+async fn serve_metrics(addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr).await
+        .with_context(|| format!("Failed to listen on {}", addr))?;
+    let route = warp::path!("metrics").and(warp::get()).map(render_metrics);
+    tracing::info!("Serving metrics on http://{}/metrics", addr);
+    tokio::spawn(warp::serve(route).run_incoming(TcpListenerStream::new(listener)));
+    Ok(())
+}
+
 // Helper to pass state to handlers
 fn with_state(
     state: Arc<ApiState>,
@@ -89,228 +392,537 @@ fn with_state(
     warp::any().map(move || Arc::clone(&state))
 }
 
+// A JSON body checked against the schema of its type before it is read, so every problem is
+// reported with the path of the offending field
+fn checked_json<T: DeserializeOwned + JsonSchema + Send>() -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    let validator = Arc::new(SchemaValidator::of::<T>());
+    warp::body::json::<serde_json::Value>().and_then(move |body: serde_json::Value| {
+        let validator = Arc::clone(&validator);
+        async move {
+            let errors = validator.check(&body);
+            if !errors.is_empty() {
+                return Err(Rejection::from(ApiError::InvalidFields(errors)));
+            }
+            serde_json::from_value(body).map_err(|err| ApiError::InvalidRequest(err.to_string()).into())
+        }
+    })
+}
+
+// The raw bundle, sent either as the `bundle` part of a multipart/form-data upload or, for any
+// other content type, as the request body itself
+fn bundle_upload() -> impl Filter<Extract = (Vec<u8>,), Error = Rejection> + Clone {
+    // Decided by the content type up front, so a broken multipart upload is reported as such
+    // instead of being read again as a raw body
+    let content_type = |multipart: bool| {
+        warp::header::optional::<String>("content-type")
+            .and_then(move |value: Option<String>| async move {
+                let is_multipart = value.is_some_and(|value| value.to_ascii_lowercase().starts_with("multipart/form-data"));
+                if is_multipart == multipart { Ok(()) } else { Err(warp::reject()) }
+            })
+            .untuple_one()
+    };
+    let multipart = content_type(true)
+        .and(warp::multipart::form().max_length(MAX_BUNDLE_SIZE))
+        .and_then(read_bundle_part);
+    let body = content_type(false)
+        .and(warp::body::content_length_limit(MAX_BUNDLE_SIZE))
+        .and(warp::body::bytes())
+        .map(|body: Bytes| body.to_vec());
+    multipart.or(body).unify()
+}
+
+async fn read_bundle_part(mut form: FormData) -> Result<Vec<u8>, Rejection> {
+    let malformed = |err: warp::Error| ApiError::InvalidRequest(format!("Malformed multipart upload: {}", err));
+    while let Some(part) = form.try_next().await.map_err(malformed)? {
+        if part.name() != "bundle" {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        let mut stream = std::pin::pin!(part.stream());
+        while let Some(mut chunk) = stream.try_next().await.map_err(malformed)? {
+            while chunk.has_remaining() {
+                let len = chunk.chunk().len();
+                bytes.extend_from_slice(chunk.chunk());
+                chunk.advance(len);
+            }
+        }
+        return Ok(bytes);
+    }
+    Err(ApiError::InvalidRequest("Multipart upload has no `bundle` part".to_string()).into())
+}
+
+// Identify the caller and let the request through if their role allows `action`;
+// the decision either way goes to the audit log
+fn authorize(
+    state: Arc<ApiState>,
+    action: Action,
+) -> impl Filter<Extract = (Principal,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-api-key")
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::method())
+        .and(warp::path::full())
+        .and(with_state(state))
+        .and_then(move |api_key: Option<String>, authorization: Option<String>, method: Method, path: warp::path::FullPath, state: Arc<ApiState>| async move {
+            let target = format!("{} {}", method, path.as_str());
+            let principal = match state.auth.authenticate(api_key.as_deref(), authorization.as_deref()) {
+                Ok(principal) => principal,
+                Err(err) => {
+                    state.audit.record(None, action, &target, Outcome::Denied, Some(err.to_string()));
+                    return Err(warp::reject::custom(err));
+                },
+            };
+            if !principal.role.allows(action) {
+                let err = ApiError::Forbidden(format!("The {} role may not {}", principal.role.label(), action.label()));
+                state.audit.record(Some(&principal), action, &target, Outcome::Denied, Some(err.to_string()));
+                return Err(warp::reject::custom(err));
+            }
+            state.audit.record(Some(&principal), action, &target, Outcome::Allowed, None);
+            Ok(principal)
+        })
+}
+
+// `authorize` for routes that do not care who the caller is
+fn require(
+    state: Arc<ApiState>,
+    action: Action,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    authorize(state, action).map(|_principal: Principal| ()).untuple_one()
+}
+
+// Manufacturers may only prove their own certifications; refusals are audited like any other
+fn check_may_prove(state: &ApiState, principal: &Principal, certifications: &[MaterialCertification]) -> Result<(), ApiError> {
+    for cert in certifications {
+        if !principal.may_prove(cert) {
+            let err = ApiError::Forbidden(format!(
+                "{} may not prove certificates of manufacturer {}",
+                principal.subject, cert.material.manufacturer
+            ));
+            state.audit.record(Some(principal), Action::Prove, &cert.certificate_id, Outcome::Denied, Some(err.to_string()));
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+// Certifications are checked before they take up a proof slot; paths are relative to the request body
+fn check_valid<'a>(certifications: impl IntoIterator<Item = (String, &'a MaterialCertification)>) -> Result<(), ApiError> {
+    let errors: Vec<_> = certifications.into_iter()
+        .flat_map(|(field, cert)| validation::validate(cert).into_iter().map(move |error| error.within(&field)))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::InvalidFields(errors))
+    }
+}
+
+// Store backends wait on disk, SQLite or S3, proof checks and proving keep a CPU busy, so all of
+// them run on the blocking pool rather than on one of the runtime's workers, which would stop
+// serving other requests meanwhile
+async fn blocking<T: Send + 'static>(task: impl FnOnce() -> T + Send + 'static) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(task).await
+        .map_err(|err| ApiError::Internal(format!("Background task failed: {}", err)))
+}
+
+async fn prove_blocking<T: Send + 'static>(prove: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T, ApiError> {
+    blocking(prove).await?.map_err(|err| ApiError::ProvingFailed(err.to_string()))
+}
+
+// A stored proof record of the caller's organisation
+async fn load_proof(tenant: &Arc<Tenant>, proof_id: &ProofId) -> Result<ProofRecord, ApiError> {
+    let (store, id) = (Arc::clone(&tenant.store), proof_id.clone());
+    blocking(move || store.get_proof(&id)).await?
+        .map_err(|err| ApiError::from_record_load(err, proof_id.as_str()))?
+        .ok_or_else(|| ApiError::ProofNotFound(proof_id.to_string()))
+}
+
+// Everything a caller reads or writes goes through their organisation's tenant
+fn tenant(state: &ApiState, principal: &Principal) -> Result<Arc<Tenant>, ApiError> {
+    state.tenants.get(&principal.org).map_err(|err| ApiError::Internal(format!("{:#}", err)))
+}
+
+// Ids taken from the URL are checked before they get anywhere near the filesystem
+fn parse_id(value: &str) -> Result<ProofId, ApiError> {
+    value.parse().map_err(|err: types::InvalidProofId| ApiError::InvalidRequest(err.to_string()))
+}
+
 // Handler for proof generation
 async fn handle_generate_proof(
+    principal: Principal,
     request: GenerateProofRequest,
     state: Arc<ApiState>,
-) -> Result<impl Reply, Infallible> {
-    tracing::info!("Received request to generate proof for material: {}", request.certification.certificate_id);
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to generate {:?} proof for material: {}", request.mode, request.certification.certificate_id);
+    metrics::record_request("generate-proof");
+    check_may_prove(&state, &principal, std::slice::from_ref(&request.certification))?;
+    check_valid([("certification".to_string(), &request.certification)])?;
+    let tenant = tenant(&state, &principal)?;
     
-    // Create a temporary circuit (empty tuple in our case)
-    let circuit = ();
+    // Returning a proof that already exists costs nothing, so the quota is only charged after
+    // looking for one, and refunded if proving fails
+    let existing = if request.force {
+        None
+    } else {
+        let (store, cert) = (Arc::clone(&tenant.store), request.certification.clone());
+        prove_blocking(move || circuit::existing_proof(store.as_ref(), &cert, request.mode)).await?
+    };
+    let generated = match existing {
+        Some(existing) => existing,
+        None => {
+            tenant.reserve(1)?;
+            let _queued = metrics::QueueGuard::enter();
+            let generated = match Arc::clone(&state.proof_slots).acquire_owned().await {
+                Ok(slot) => {
+                    let tenant = Arc::clone(&tenant);
+                    let cert = request.certification.clone();
+                    prove_blocking(move || {
+                        let _slot = slot;
+                        circuit::generate_proof(tenant.store.as_ref(), &tenant.work_dir, &cert, request.mode, request.force)
+                    })
+                    .await
+                }
+                Err(err) => Err(ApiError::Internal(err.to_string())),
+            };
+            let generated = generated.inspect_err(|_| tenant.refund(1))?;
+            // A concurrent request may have stored the same proof first, which costs nothing
+            if generated.reused {
+                tenant.refund(1);
+            }
+            generated
+        }
+    };
+    tracing::info!("Proof ready: {} (reused: {})", generated.proof_id, generated.reused);
     
-    // Call the circuit module to generate proof
-    match circuit::generate_proof(&circuit, &state.output_dir, &request.certification) {
-        Ok(proof_path) => {
-            tracing::info!("Proof generated successfully: {}", proof_path);
-            
-            // For the hackathon MVP, return the proof path as the ID
-            Ok(json(&GenerateProofResponse {
-                proof_id: proof_path,
-                status: "success".to_string(),
-                message: "Proof generated successfully".to_string(),
-            }))
-        },
-        Err(err) => {
-            tracing::error!("Failed to generate proof: {:?}", err);
-            
-            Ok(json(&GenerateProofResponse {
-                proof_id: "".to_string(),
-                status: "error".to_string(),
-                message: format!("Failed to generate proof: {}", err),
-            }))
+    let message = if generated.reused {
+        "Existing proof returned for identical certification".to_string()
+    } else {
+        "Proof generated successfully".to_string()
+    };
+    Ok(json(&GenerateProofResponse {
+        proof_id: generated.proof_id,
+        status: "success".to_string(),
+        message,
+        reused: generated.reused,
+    }))
+}
+
+// Handler for batch proof generation
+async fn handle_generate_batch_proof(
+    principal: Principal,
+    request: GenerateBatchProofRequest,
+    state: Arc<ApiState>,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to generate {:?} batch proof for {} certifications", request.mode, request.certifications.len());
+    metrics::record_request("generate-batch-proof");
+    let _queued = metrics::QueueGuard::enter();
+    
+    if request.certifications.is_empty() {
+        return Err(ApiError::InvalidRequest("A batch needs at least one certification".to_string()).into());
+    }
+    check_may_prove(&state, &principal, &request.certifications)?;
+    check_valid(request.certifications.iter().enumerate().map(|(index, cert)| (format!("certifications[{}]", index), cert)))?;
+    let duplicates = validation::duplicate_certificates(&request.certifications);
+    if !duplicates.is_empty() {
+        return Err(ApiError::InvalidFields(duplicates).into());
+    }
+    let tenant = tenant(&state, &principal)?;
+    // Charged once the batch is known to be valid, and refunded if proving fails
+    tenant.reserve(request.certifications.len())?;
+    let batch_id = match Arc::clone(&state.proof_slots).acquire_owned().await {
+        Ok(slot) => {
+            let tenant = Arc::clone(&tenant);
+            let certs = request.certifications.clone();
+            prove_blocking(move || {
+                let _slot = slot;
+                batch::generate_batch_proof(tenant.store.as_ref(), &tenant.work_dir, &certs, request.mode)
+            })
+            .await
         }
+        Err(err) => Err(ApiError::Internal(err.to_string())),
+    };
+    let batch_id = batch_id.inspect_err(|_| tenant.refund(request.certifications.len()))?;
+    tracing::info!("Batch proof generated successfully: {}", batch_id);
+    
+    Ok(json(&GenerateProofResponse {
+        proof_id: batch_id,
+        status: "success".to_string(),
+        message: format!("Batch proof generated for {} certifications", request.certifications.len()),
+        reused: false,
+    }))
+}
+
+// Handler for single-certificate checks against a batch
+async fn handle_batch_inclusion(
+    batch_id: String,
+    certificate_id: String,
+    principal: Principal,
+    state: Arc<ApiState>,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to check certificate {} in batch {}", certificate_id, batch_id);
+    metrics::record_request("batch-inclusion");
+    
+    let batch_id = parse_id(&batch_id)?;
+    let tenant = tenant(&state, &principal)?;
+    let (store, id) = (Arc::clone(&tenant.store), batch_id.clone());
+    let record = blocking(move || store.get_batch(&id)).await?
+        .map_err(|err| ApiError::from_record_load(err, batch_id.as_str()))?
+        .ok_or_else(|| ApiError::BatchNotFound(batch_id.to_string()))?;
+    
+    if !record.entries.iter().any(|entry| entry.certificate_id == certificate_id) {
+        return Err(ApiError::CertificateNotInBatch { batch_id: batch_id.to_string(), certificate_id }.into());
     }
+    
+    let (store, work_dir) = (Arc::clone(&tenant.store), tenant.work_dir.clone());
+    let inclusion = blocking(move || {
+        evm::ensure_setup(store.as_ref(), &work_dir, record.mode);
+        batch::verify_batch_inclusion(&record, &certificate_id, &work_dir)
+    }).await?
+        .map_err(|err| ApiError::VerificationFailed(err.to_string()))?;
+    Ok(json(&inclusion))
+}
+
+// Handler for preflight checks
+async fn handle_preflight(
+    principal: Principal,
+    request: PreflightRequest,
+    state: Arc<ApiState>,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received preflight request for material: {}", request.certification.certificate_id);
+    metrics::record_request("preflight");
+    check_may_prove(&state, &principal, std::slice::from_ref(&request.certification))?;
+    check_valid([("certification".to_string(), &request.certification)])?;
+    
+    // Emulation runs the whole guest, so it takes a proving slot like any other proof
+    let report = match Arc::clone(&state.proof_slots).acquire_owned().await {
+        Ok(slot) => {
+            let cert = request.certification.clone();
+            blocking(move || {
+                let _slot = slot;
+                circuit::preflight(&cert, request.mode)
+            })
+            .await?
+            .map_err(|err| ApiError::InvalidCertification(err.to_string()))
+        }
+        Err(err) => Err(ApiError::Internal(err.to_string())),
+    }?;
+    tracing::info!("Preflight completed in {} cycles", report.cycles);
+    Ok(json(&report))
+}
+
+// Handler for certification validation; an invalid certification is a result, not an error
+async fn handle_validate(body: Bytes) -> Result<impl Reply, Rejection> {
+    metrics::record_request("validate");
+    let report = validation::validate_json("request".to_string(), &body);
+    tracing::info!("Validated certification {:?}: {} problems", report.certificate_id, report.errors.len());
+    Ok(json(&report))
 }
 
-// Handler for proof verification
 // Handler for proof verification
 async fn handle_verify_proof(
-  request: VerifyProofRequest,
-  state: Arc<ApiState>,
-) -> Result<impl Reply, Infallible> {
-  tracing::info!("Received request to verify proof: {}", request.proof_id);
-  
-  // Construct the full path to the proof file
-  let proof_path = state.output_dir.join(format!("{}.json", request.proof_id));
-  
-  // Check if proof file exists
-  if !proof_path.exists() {
-      tracing::error!("Proof file not found: {:?}", proof_path);
-      
-      // Return error response
-      let error_result = VerificationResult {
-          material_id: request.certification_id.clone(),
-          material_type: "unknown".to_string(),
-          standard: "unknown".to_string(),
-          timestamp: chrono::Utc::now().to_rfc3339(),
-          verification_id: "error".to_string(),
-          overall_compliance: "ERROR".to_string(),
-          properties: std::collections::HashMap::new(),
-          compliance_summary: ComplianceSummary {
-              total_properties: 0,
-              public_properties: 0,
-              private_properties: 0,
-              passing_properties: 0,
-              failing_properties: 0,
-          },
-          zkp_info: ZkpInfo {
-              implemented: false,
-              proof_type: "".to_string(),
-              circuit: "".to_string(),
-              proof: "".to_string(),
-              public_signals: "".to_string(),
-              verified: false,
-          },
-      };
-      
-      return Ok(json(&error_result));
-  }
-  
-  // Read the proof file
-  let proof_content = match std::fs::read_to_string(&proof_path) {
-      Ok(content) => content,
-      Err(err) => {
-          tracing::error!("Failed to read proof file: {:?}", err);
-          
-          // Return error response
-          let error_result = VerificationResult {
-              material_id: request.certification_id.clone(),
-              material_type: "unknown".to_string(),
-              standard: "unknown".to_string(),
-              timestamp: chrono::Utc::now().to_rfc3339(),
-              verification_id: "error".to_string(),
-              overall_compliance: "ERROR".to_string(),
-              properties: std::collections::HashMap::new(),
-              compliance_summary: ComplianceSummary {
-                  total_properties: 0,
-                  public_properties: 0,
-                  private_properties: 0,
-                  passing_properties: 0,
-                  failing_properties: 0,
-              },
-              zkp_info: ZkpInfo {
-                  implemented: false,
-                  proof_type: "".to_string(),
-                  circuit: "".to_string(),
-                  proof: "".to_string(),
-                  public_signals: "".to_string(),
-                  verified: false,
-              },
-          };
-          
-          return Ok(json(&error_result));
-      }
-  };
-  
-  // Parse the proof content
-  let proof_data: serde_json::Value = match serde_json::from_str(&proof_content) {
-      Ok(data) => data,
-      Err(err) => {
-          tracing::error!("Failed to parse proof content: {:?}", err);
-          
-          // Return error response
-          let error_result = VerificationResult {
-              material_id: request.certification_id.clone(),
-              material_type: "unknown".to_string(),
-              standard: "unknown".to_string(),
-              timestamp: chrono::Utc::now().to_rfc3339(),
-              verification_id: "error".to_string(),
-              overall_compliance: "ERROR".to_string(),
-              properties: std::collections::HashMap::new(),
-              compliance_summary: ComplianceSummary {
-                  total_properties: 0,
-                  public_properties: 0,
-                  private_properties: 0,
-                  passing_properties: 0,
-                  failing_properties: 0,
-              },
-              zkp_info: ZkpInfo {
-                  implemented: false,
-                  proof_type: "".to_string(),
-                  circuit: "".to_string(),
-                  proof: "".to_string(),
-                  public_signals: "".to_string(),
-                  verified: false,
-              },
-          };
-          
-          return Ok(json(&error_result));
-      }
-  };
-  
-  // Extract certification data from the proof file
-  let certification: MaterialCertification = match serde_json::from_value(proof_data["certification"].clone()) {
-      Ok(cert) => cert,
-      Err(err) => {
-          tracing::error!("Failed to extract certification data: {:?}", err);
-          
-          // Return error response
-          let error_result = VerificationResult {
-              material_id: request.certification_id.clone(),
-              material_type: "unknown".to_string(),
-              standard: "unknown".to_string(),
-              timestamp: chrono::Utc::now().to_rfc3339(),
-              verification_id: "error".to_string(),
-              overall_compliance: "ERROR".to_string(),
-              properties: std::collections::HashMap::new(),
-              compliance_summary: ComplianceSummary {
-                  total_properties: 0,
-                  public_properties: 0,
-                  private_properties: 0,
-                  passing_properties: 0,
-                  failing_properties: 0,
-              },
-              zkp_info: ZkpInfo {
-                  implemented: false,
-                  proof_type: "".to_string(),
-                  circuit: "".to_string(),
-                  proof: "".to_string(),
-                  public_signals: "".to_string(),
-                  verified: false,
-              },
-          };
-          
-          return Ok(json(&error_result));
-      }
-  };
-  
-  // Actually verify the proof using our circuit module
-  match circuit::verify_proof(&proof_path.to_string_lossy(), &state.output_dir, &certification) {
-      Ok(verification_result) => {
-          tracing::info!("Proof verified: {}", verification_result.verification_id);
-          Ok(json(&verification_result))
-      },
-      Err(err) => {
-          tracing::error!("Failed to verify proof: {:?}", err);
-          
-          // Create a basic error response that matches the VerificationResult structure
-          let error_result = VerificationResult {
-              material_id: certification.certificate_id,
-              material_type: certification.material.r#type,
-              standard: certification.material.designation,
-              timestamp: chrono::Utc::now().to_rfc3339(),
-              verification_id: "error".to_string(),
-              overall_compliance: "ERROR".to_string(),
-              properties: std::collections::HashMap::new(),
-              compliance_summary: ComplianceSummary {
-                  total_properties: 0,
-                  public_properties: 0,
-                  private_properties: 0,
-                  passing_properties: 0,
-                  failing_properties: 0,
-              },
-              zkp_info: ZkpInfo {
-                  implemented: false,
-                  proof_type: "".to_string(),
-                  circuit: "".to_string(),
-                  proof: "".to_string(),
-                  public_signals: "".to_string(),
-                  verified: false,
-              },
-          };
-          
-          Ok(json(&error_result))
-      }
-  }
-}
\ No newline at end of file
+    principal: Principal,
+    request: VerifyProofRequest,
+    state: Arc<ApiState>,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to verify proof: {}", request.proof_id);
+    metrics::record_request("verify-proof");
+    
+    // Load the proof record, which carries the certification it was generated for
+    let tenant = tenant(&state, &principal)?;
+    let record = load_proof(&tenant, &request.proof_id).await?;
+    
+    if record.certification.certificate_id != request.certification_id {
+        return Err(ApiError::InvalidCertification(format!(
+            "Proof {} does not cover certificate {}",
+            request.proof_id, request.certification_id
+        )).into());
+    }
+    
+    // Actually verify the proof using our circuit module
+    let (store, work_dir) = (Arc::clone(&tenant.store), tenant.work_dir.clone());
+    let verification_result = blocking(move || {
+        evm::ensure_setup(store.as_ref(), &work_dir, record.mode);
+        circuit::verify_proof(&record, &record.certification, &work_dir)
+    }).await?
+        .map_err(|err| ApiError::VerificationFailed(err.to_string()))?;
+    tracing::info!("Proof verified: {}", verification_result.verification_id);
+    Ok(json(&verification_result))
+}
+
+// Handler for legacy snarkjs proof verification
+async fn handle_verify_legacy_proof(
+    bundle: LegacyProofBundle,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to verify legacy proof with {} public signals", bundle.public_signals.len());
+    metrics::record_request("verify-legacy-proof");
+    
+    // The pairing check is CPU-bound, so it stays off the async workers
+    let verification_result = blocking(move || legacy::verify_legacy_proof(&bundle)).await?
+        .map_err(|err| ApiError::VerificationFailed(err.to_string()))?;
+    tracing::info!("Legacy proof checked: {}", verification_result.verification_id);
+    Ok(json(&verification_result))
+}
+
+// Handler for uploaded bundle verification
+async fn handle_verify_bundle(
+    query: VerifyBundleQuery,
+    upload: Vec<u8>,
+    state: Arc<ApiState>,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to verify an uploaded proof bundle ({} bytes)", upload.len());
+    metrics::record_request("verify-bundle");
+    
+    let proof_bundle = bundle::parse_bundle(&upload)
+        .map_err(|err| ApiError::InvalidRequest(format!("Not a proof bundle: {}", err)))?;
+    // Only bundles signed by this server or one it is configured to trust count; a key in the
+    // query can narrow that down but not add to it
+    let mut trusted: Vec<String> = std::iter::once(state.issuer.public_key())
+        .chain(state.trusted_issuers.iter().cloned())
+        .collect();
+    if let Some(key) = &query.issuer_key {
+        trusted.retain(|trusted| trusted.eq_ignore_ascii_case(key.trim()));
+        if trusted.is_empty() {
+            return Err(ApiError::VerificationFailed(format!("{} is not a trusted issuer", key.trim())).into());
+        }
+    }
+    let proof_id = proof_bundle.payload.proof_id.clone();
+    let checked = blocking(move || bundle::verify_bundle(&proof_bundle, &trusted, query.program_hash.as_deref())).await?
+        .map_err(|err| ApiError::VerificationFailed(err.to_string()))?;
+    tracing::info!("Bundle {} verified: {} signed by {}", proof_id, checked.verification.overall_compliance, checked.issuer);
+    Ok(json(&checked))
+}
+
+// Handler for Solidity verifier export
+async fn handle_evm_export(
+    proof_id: String,
+    principal: Principal,
+    state: Arc<ApiState>,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to export Solidity verifier for proof: {}", proof_id);
+    metrics::record_request("solidity-export");
+    
+    let proof_id = parse_id(&proof_id)?;
+    let tenant = tenant(&state, &principal)?;
+    let record = load_proof(&tenant, &proof_id).await?;
+    
+    if record.mode != ProvingMode::Evm {
+        return Err(ApiError::WrongProofMode {
+            proof_id: proof_id.to_string(),
+            mode: record.mode.label().to_string(),
+            required: ProvingMode::Evm.label().to_string(),
+        }.into());
+    }
+    
+    let (store, work_dir) = (Arc::clone(&tenant.store), tenant.work_dir.clone());
+    let export = blocking(move || {
+        evm::ensure_setup(store.as_ref(), &work_dir, record.mode);
+        evm::export_verifier(&work_dir, &record)
+    }).await?
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+    Ok(json(&export))
+}
+
+// Handler for fetching a proof record
+async fn handle_get_proof(
+    proof_id: String,
+    principal: Principal,
+    state: Arc<ApiState>,
+) -> Result<impl Reply, Rejection> {
+    metrics::record_request("get-proof");
+    
+    let proof_id = parse_id(&proof_id)?;
+    let record = load_proof(&tenant(&state, &principal)?, &proof_id).await?;
+    if principal.may_see_private(&record.certification) {
+        Ok(json(&record))
+    } else {
+        Ok(json(&record.redacted()))
+    }
+}
+
+// Handler for the guest version registry
+async fn handle_guest_versions() -> Result<impl Reply, Rejection> {
+    metrics::record_request("guest-versions");
+    Ok(json(&registry::versions()))
+}
+
+// Handler for the published JSON Schemas
+async fn handle_schema(name: String) -> Result<impl Reply, Rejection> {
+    metrics::record_request("schema");
+    let kind = SchemaType::from_name(&name).ok_or(ApiError::SchemaNotFound(name))?;
+    Ok(warp::reply::with_header(json(&kind.schema()), "Content-Type", "application/schema+json"))
+}
+
+// Handler for proof listings
+async fn handle_list_proofs(
+    principal: Principal,
+    query: ProofQuery,
+    state: Arc<ApiState>,
+) -> Result<impl Reply, Rejection> {
+    metrics::record_request("list-proofs");
+    
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return Err(ApiError::InvalidRequest("`from` must not be after `to`".to_string()).into());
+        }
+    }
+    
+    // Summaries carry no property values, so every caller gets the same listing
+    let store = Arc::clone(&tenant(&state, &principal)?.store);
+    let page = blocking(move || store.list_proofs(&query)).await?
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+    Ok(json(&page))
+}
+
+// Handler for deleting a proof and its artifacts
+async fn handle_delete_proof(
+    proof_id: String,
+    principal: Principal,
+    state: Arc<ApiState>,
+) -> Result<impl Reply, Rejection> {
+    metrics::record_request("delete-proof");
+    
+    let proof_id = parse_id(&proof_id)?;
+    let tenant = tenant(&state, &principal)?;
+    let (store, id) = (Arc::clone(&tenant.store), proof_id.clone());
+    let existed = blocking(move || store.delete_proof(&id)).await?
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+    if !existed {
+        return Err(ApiError::ProofNotFound(proof_id.to_string()).into());
+    }
+    
+    // Recursion artifacts of full proofs live in the working directory, not the store
+    let artifacts_dir = proof_id.artifacts_dir(&tenant.work_dir);
+    if artifacts_dir.is_dir() {
+        if let Err(err) = tokio::fs::remove_dir_all(&artifacts_dir).await {
+            tracing::warn!("Failed to remove artifacts of proof {}: {}", proof_id, err);
+        }
+    }
+    
+    tracing::info!("Deleted proof {}", proof_id);
+    Ok(warp::reply::with_status(warp::reply(), warp::http::StatusCode::NO_CONTENT))
+}
+
+// Handler for proof bundle downloads
+async fn handle_bundle(
+    proof_id: String,
+    principal: Principal,
+    state: Arc<ApiState>,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to bundle proof: {}", proof_id);
+    metrics::record_request("bundle");
+    
+    let proof_id = parse_id(&proof_id)?;
+    let tenant = tenant(&state, &principal)?;
+    let record = load_proof(&tenant, &proof_id).await?;
+    
+    let (state, store, work_dir) = (Arc::clone(&state), Arc::clone(&tenant.store), tenant.work_dir.clone());
+    let bundle = blocking(move || {
+        evm::ensure_setup(store.as_ref(), &work_dir, record.mode);
+        bundle::create_bundle(&record, &state.issuer, &work_dir)
+    }).await?
+        .map_err(|err| ApiError::BundleUnavailable(err.to_string()))?;
+    Ok(warp::reply::with_header(
+        json(&bundle),
+        "Content-Disposition",
+        format!("attachment; filename=\"{}.bundle.json\"", proof_id),
+    ))
+}
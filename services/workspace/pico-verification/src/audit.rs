@@ -0,0 +1,76 @@
+//! Append-only record of who did what through the API.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use crate::auth::{Action, Principal, Role};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Allowed,
+    Denied,
+}
+
+/// One line of the audit log
+#[derive(Debug, Serialize)]
+pub struct AuditEvent<'a> {
+    pub timestamp: String,
+    /// `None` when the caller could not be identified
+    pub subject: Option<&'a str>,
+    pub org: Option<&'a str>,
+    pub role: Option<Role>,
+    pub action: &'static str,
+    /// What was acted on, e.g. `POST /api/generate-proof` or a certificate id
+    pub target: &'a str,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// JSON lines appended to a file, one per access decision
+pub struct AuditLog {
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {:?}", path))?;
+        Ok(AuditLog { file: Mutex::new(file) })
+    }
+
+    pub fn record(&self, principal: Option<&Principal>, action: Action, target: &str, outcome: Outcome, reason: Option<String>) {
+        let event = AuditEvent {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            subject: principal.map(|p| p.subject.as_str()),
+            org: principal.map(|p| p.org.as_str()),
+            role: principal.map(|p| p.role),
+            action: action.label(),
+            target,
+            outcome,
+            reason,
+        };
+        // A failed audit write is logged rather than failing the request it describes
+        let written = serde_json::to_string(&event)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| {
+                let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                writeln!(file, "{}", line)?;
+                file.flush()?;
+                Ok(())
+            });
+        if let Err(err) = written {
+            tracing::error!("Failed to write audit event for {}: {}", target, err);
+        }
+    }
+}
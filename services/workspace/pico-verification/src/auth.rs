@@ -0,0 +1,255 @@
+//! Who is calling the API and what they are allowed to do.
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use crate::error::ApiError;
+use crate::types::{MaterialCertification, OrgId};
+
+/// What a caller is, as granted by their API key or token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Proves certifications issued in its own name
+    Manufacturer,
+    /// Issues signed proof bundles
+    Lab,
+    /// Checks proofs and bundles
+    Inspector,
+    /// Everything, including deleting proofs and managing registries
+    Admin,
+}
+
+impl Role {
+    pub fn label(self) -> &'static str {
+        match self {
+            Role::Manufacturer => "manufacturer",
+            Role::Lab => "lab",
+            Role::Inspector => "inspector",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn allows(self, action: Action) -> bool {
+        match (self, action) {
+            (Role::Admin, _) => true,
+            // Deliberately open to every authenticated role: checking a proof changes nothing, and
+            // callers still only see their own organisation's proofs, with private values redacted
+            // unless they may prove the certification
+            (_, Action::Verify) => true,
+            (Role::Manufacturer, Action::Prove) => true,
+            (Role::Lab, Action::Sign) => true,
+            _ => false,
+        }
+    }
+}
+
+/// What a route does, for deciding which roles may call it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Generate proofs or dry-run the guest
+    Prove,
+    /// Issue signed proof bundles
+    Sign,
+    /// Look up and check proofs; every role may
+    Verify,
+    /// Delete proofs, change registries and read the server's metrics
+    Manage,
+}
+
+impl Action {
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Prove => "prove",
+            Action::Sign => "sign",
+            Action::Verify => "verify",
+            Action::Manage => "manage",
+        }
+    }
+}
+
+/// An authenticated caller
+#[derive(Debug, Clone)]
+pub struct Principal {
+    /// Key name or token subject
+    pub subject: String,
+    /// The organisation whose data the caller works with; admins manage only their own
+    pub org: OrgId,
+    pub role: Role,
+    /// The manufacturer a manufacturer principal acts for
+    pub manufacturer: Option<String>,
+}
+
+impl Principal {
+    /// Manufacturers may only prove certifications that name them as the manufacturer
+    pub fn may_prove(&self, cert: &MaterialCertification) -> bool {
+        match self.role {
+            Role::Admin => true,
+            Role::Manufacturer => self.manufacturer.as_deref() == Some(cert.material.manufacturer.as_str()),
+            _ => false,
+        }
+    }
+
+    /// Private property values of stored proofs are shown to admins and the manufacturer that
+    /// certified them; everyone else sees them [redacted](MaterialCertification::redacted)
+    pub fn may_see_private(&self, cert: &MaterialCertification) -> bool {
+        self.may_prove(cert)
+    }
+}
+
+/// One entry of the API key file; only the SHA-256 of each key is kept
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKey {
+    pub name: String,
+    /// Hex SHA-256 of the key, e.g. from `printf %s "$KEY" | sha256sum`
+    pub key_sha256: String,
+    /// Keys from before organisations belong to the default one
+    #[serde(default)]
+    pub org: OrgId,
+    pub role: Role,
+    /// Required for manufacturer keys
+    #[serde(default)]
+    pub manufacturer: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+/// Claims expected in an HS256 bearer token
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    #[serde(default)]
+    pub org: OrgId,
+    pub role: Role,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+    /// Expiry, in seconds since the Unix epoch
+    pub exp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+}
+
+/// Checks API keys (`X-API-Key`) and HS256 JWTs (`Authorization: Bearer`)
+pub struct Authenticator {
+    api_keys: Vec<ApiKey>,
+    jwt_secret: Option<Vec<u8>>,
+    /// Every caller is treated as an admin of the default organisation
+    disabled: bool,
+}
+
+impl Authenticator {
+    pub fn new(api_keys: Vec<ApiKey>, jwt_secret: Option<String>) -> Result<Self> {
+        for key in &api_keys {
+            let valid_hash = key.key_sha256.len() == 64 && key.key_sha256.bytes().all(|b| b.is_ascii_hexdigit());
+            if !valid_hash {
+                anyhow::bail!("API key {} must give key_sha256 as 64 hex characters", key.name);
+            }
+            if key.role == Role::Manufacturer && key.manufacturer.is_none() {
+                anyhow::bail!("API key {} has the manufacturer role but names no manufacturer", key.name);
+            }
+        }
+        if api_keys.is_empty() && jwt_secret.is_none() {
+            anyhow::bail!("No API keys or JWT secret configured; pass --api-keys or --jwt-secret, or --insecure-no-auth for local development");
+        }
+
+        Ok(Authenticator {
+            api_keys,
+            jwt_secret: jwt_secret.map(String::into_bytes),
+            disabled: false,
+        })
+    }
+
+    /// Read the API key file at `api_keys`, a JSON array of [`ApiKey`]
+    pub fn load(api_keys: Option<&Path>, jwt_secret: Option<String>) -> Result<Self> {
+        let keys = match api_keys {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read API keys from {:?}", path))?;
+                serde_json::from_str(&content).with_context(|| format!("Invalid API key file {:?}", path))?
+            },
+            None => Vec::new(),
+        };
+        Self::new(keys, jwt_secret)
+    }
+
+    /// Let every request through as an admin of the default organisation; for local development only
+    pub fn disabled() -> Self {
+        Authenticator { api_keys: Vec::new(), jwt_secret: None, disabled: true }
+    }
+
+    /// Identify the caller from the `X-API-Key` and `Authorization` headers
+    pub fn authenticate(&self, api_key: Option<&str>, authorization: Option<&str>) -> Result<Principal, ApiError> {
+        if self.disabled {
+            return Ok(Principal {
+                subject: "anonymous".to_string(),
+                org: OrgId::default(),
+                role: Role::Admin,
+                manufacturer: None,
+            });
+        }
+
+        if let Some(key) = api_key {
+            let digest = Sha256::digest(key.as_bytes());
+            // Check every entry so the time taken does not tell which key came close
+            let matched = self.api_keys.iter().fold(None, |found, entry| {
+                let expected = hex::decode(&entry.key_sha256).unwrap_or_default();
+                let equal = expected.len() == digest.len()
+                    && expected.iter().zip(digest.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0;
+                found.or(equal.then_some(entry))
+            });
+            return matched
+                .map(|entry| Principal {
+                    subject: entry.name.clone(),
+                    org: entry.org.clone(),
+                    role: entry.role,
+                    manufacturer: entry.manufacturer.clone(),
+                })
+                .ok_or_else(|| ApiError::Unauthorized("Unknown API key".to_string()));
+        }
+
+        match authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+            Some(token) => {
+                let claims = self.verify_token(token.trim())?;
+                Ok(Principal { subject: claims.sub, org: claims.org, role: claims.role, manufacturer: claims.manufacturer })
+            },
+            None => Err(ApiError::Unauthorized("Send an X-API-Key header or a bearer token".to_string())),
+        }
+    }
+
+    fn verify_token(&self, token: &str) -> Result<Claims, ApiError> {
+        let invalid = |reason: &str| ApiError::Unauthorized(format!("Invalid bearer token: {}", reason));
+        let secret = self.jwt_secret.as_deref().ok_or_else(|| invalid("bearer tokens are not accepted by this server"))?;
+
+        let (signed, signature) = token.rsplit_once('.').ok_or_else(|| invalid("not a JWT"))?;
+        let (header, claims) = signed.split_once('.').ok_or_else(|| invalid("not a JWT"))?;
+        let decode = |segment: &str| URL_SAFE_NO_PAD.decode(segment).map_err(|_| invalid("malformed encoding"));
+
+        let header: JwtHeader = serde_json::from_slice(&decode(header)?).map_err(|_| invalid("malformed header"))?;
+        if header.alg != "HS256" {
+            return Err(invalid("only HS256 is supported"));
+        }
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+        mac.update(signed.as_bytes());
+        mac.verify_slice(&decode(signature)?).map_err(|_| invalid("bad signature"))?;
+
+        let claims: Claims = serde_json::from_slice(&decode(claims)?).map_err(|_| invalid("malformed claims"))?;
+        let now = chrono::Utc::now().timestamp();
+        if claims.exp <= now {
+            return Err(invalid("expired"));
+        }
+        if claims.nbf.is_some_and(|nbf| nbf > now) {
+            return Err(invalid("not valid yet"));
+        }
+        if claims.role == Role::Manufacturer && claims.manufacturer.is_none() {
+            return Err(invalid("manufacturer tokens must name the manufacturer"));
+        }
+        Ok(claims)
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::{Context, Result};
+use material_verification_lib::batch::{
+    certificate_commitment, merkle_path, merkle_root, verify_inclusion, BatchCertificate, BatchOutput,
+};
+use pico_sdk::client::DefaultProverClient;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Instant;
+use crate::circuit;
+use crate::evm;
+use crate::guest;
+use crate::metrics;
+use crate::registry::{self, GuestKind, OutputLayout};
+use crate::store::{self, ProofStore};
+use crate::validation;
+use crate::types::{
+    BatchEntry, BatchInclusionResult, BatchProofRecord, MaterialCertification, ProgramInfo, ProofId, ProvingMode,
+};
+
+/// Batch records live apart from single proofs since they use a different layout
+pub const BATCH_DIR: &str = "batches";
+
+/// Batch guest ELF chosen in the configuration and its program hash, run instead of the embedded one
+static BATCH_OVERRIDE: OnceLock<(Vec<u8>, String)> = OnceLock::new();
+
+/// Run the batch guest at `path` instead of the embedded one; only the first call has any effect.
+/// Returns the program hash batch proofs will be tagged with from now on.
+pub fn use_batch_elf(path: &Path) -> Result<&'static str> {
+    let elf = std::fs::read(path).with_context(|| format!("Failed to read batch guest ELF {:?}", path))?;
+    let hash = circuit::program_hash(&elf);
+    let (_, hash) = BATCH_OVERRIDE.get_or_init(|| (elf, hash));
+    Ok(hash)
+}
+
+/// The batch guest program the prover runs
+pub fn batch_elf() -> &'static [u8] {
+    BATCH_OVERRIDE.get().map_or(guest::BATCH_ELF, |(elf, _)| elf)
+}
+
+/// Hash of [`batch_elf`], which new batch proofs are tagged with and stored ones must match
+pub fn batch_program_hash() -> &'static str {
+    BATCH_OVERRIDE.get().map_or(guest::BATCH_PROGRAM_HASH, |(_, hash)| hash)
+}
+
+/// Reduce a certification to the numeric properties the guest checks, in the order the
+/// certificate guest checks them
+fn to_batch_certificate(cert: &MaterialCertification, salt: [u8; 32]) -> BatchCertificate {
+    BatchCertificate {
+        certificate_id: cert.certificate_id.clone(),
+        salt,
+        properties: circuit::checked_properties(cert).into_iter().map(|(property, _)| property).collect(),
+    }
+}
+
+/// Prove a whole batch of certifications at once and store the aggregated record
+pub fn generate_batch_proof(store: &dyn ProofStore, work_dir: &Path, certs: &[MaterialCertification], mode: ProvingMode) -> Result<ProofId> {
+    if certs.is_empty() {
+        anyhow::bail!("A batch needs at least one certification");
+    }
+    if let Some(duplicate) = validation::duplicate_certificates(certs).first() {
+        anyhow::bail!("Certificate ids in a batch must be unique: {}", duplicate);
+    }
+
+    let started = Instant::now();
+    let batch: Vec<BatchCertificate> = certs.iter()
+        .map(|cert| to_batch_certificate(cert, rand::random()))
+        .collect();
+    let commitments: Vec<[u8; 32]> = batch.iter().map(certificate_commitment).collect();
+
+    // Initialize the Pico prover client with the batch guest
+    let client = DefaultProverClient::new(batch_elf());
+    client.get_stdin_builder().borrow_mut().write(&batch);
+
+    let batch_id = ProofId::new_random();
+    store::track_job(store, &batch_id, || {
+        tracing::info!("Proving batch {} of {} certificates in {:?} mode", batch_id, certs.len(), mode);
+        let circuit::ProverOutput { pv_stream, embed_proof, evm_inputs } = circuit::prove(&client, store, work_dir, batch_program_hash(), &batch_id, mode)
+            .inspect_err(|_| metrics::record_failure("proving"))?;
+
+        // The guest must have committed to exactly the certificates we sent
+        let output = decode_batch_output(pv_stream.as_deref())?;
+        let root = merkle_root(&commitments);
+        if output.merkle_root != root || output.verdicts.len() != certs.len() {
+            metrics::record_failure("output_mismatch");
+            anyhow::bail!("Batch guest committed to a different set of certificates");
+        }
+
+        let entries = batch.iter().zip(&commitments).enumerate()
+            .map(|(index, (cert, commitment))| BatchEntry {
+                certificate_id: cert.certificate_id.clone(),
+                index,
+                salt: hex::encode(cert.salt),
+                commitment: hex::encode(commitment),
+                verdict: output.verdicts[index],
+                path: merkle_path(&commitments, index).iter().map(|node| node.map(hex::encode)).collect(),
+            })
+            .collect();
+
+        let record = BatchProofRecord {
+            id: batch_id.clone(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            mode,
+            merkle_root: hex::encode(root),
+            entries,
+            certifications: certs.to_vec(),
+            program_hash: Some(batch_program_hash().to_string()),
+            pv_stream,
+            embed_proof,
+            evm_inputs,
+        };
+
+        store.put_batch(&record)
+            .inspect_err(|_| metrics::record_failure("persist"))
+    })?;
+
+    metrics::PROVING_DURATION
+        .with_label_values(&[mode.label()])
+        .observe(started.elapsed().as_secs_f64());
+
+    Ok(batch_id)
+}
+
+/// Check one certificate of a batch against the proven Merkle root and verdicts. EVM proofs are
+/// checked against the setup in `work_dir`, where they were made.
+pub fn verify_batch_inclusion(record: &BatchProofRecord, certificate_id: &str, work_dir: &Path) -> Result<BatchInclusionResult> {
+    let version = registry::resolve(&record.id, record.program_hash.as_deref(), GuestKind::Batch)?;
+    // Batches from before ids had to be unique may repeat one, and then it is unclear which entry is meant
+    if record.entries.iter().filter(|entry| entry.certificate_id == certificate_id).count() > 1 {
+        anyhow::bail!("Certificate {} appears more than once in batch {}, so it cannot be checked on its own", certificate_id, record.id);
+    }
+    let entry = record.entries.iter()
+        .find(|entry| entry.certificate_id == certificate_id)
+        .ok_or_else(|| anyhow::anyhow!("Certificate {} is not part of batch {}", certificate_id, record.id))?;
+    let cert = record.certifications.iter()
+        .find(|cert| cert.certificate_id == certificate_id)
+        .ok_or_else(|| anyhow::anyhow!("Batch {} has no certification for {}", record.id, certificate_id))?;
+
+    let output = match version.output_layout {
+        OutputLayout::BatchV1 => decode_batch_output(record.pv_stream.as_deref())?,
+        OutputLayout::ComplianceV0 | OutputLayout::ComplianceV1 => anyhow::bail!("Guest {} proves single certificates, not batches", version.version),
+    };
+    let salt: [u8; 32] = decode_hash(&entry.salt)?;
+    let path = entry.path.iter()
+        .map(|node| node.as_deref().map(decode_hash).transpose())
+        .collect::<Result<Vec<_>>>()?;
+
+    // Recompute the leaf from the stored certification rather than trusting the recorded commitment
+    let commitment = certificate_commitment(&to_batch_certificate(cert, salt));
+    let included = hex::encode(commitment) == entry.commitment
+        && output.verdicts.len() == record.entries.len()
+        && hex::encode(output.merkle_root) == record.merkle_root
+        && verify_inclusion(&commitment, entry.index, &path, &output.merkle_root);
+
+    let verdict = included && output.verdicts.get(entry.index).copied().unwrap_or(false);
+
+    Ok(BatchInclusionResult {
+        batch_id: record.id.clone(),
+        certificate_id: certificate_id.to_string(),
+        index: entry.index,
+        overall_compliance: if verdict { "PASS".to_string() } else { "FAIL".to_string() },
+        merkle_root: record.merkle_root.clone(),
+        commitment: hex::encode(commitment),
+        path: entry.path.clone(),
+        included,
+        proof_type: format!("{} (batch of {})", record.mode.proof_type(), record.entries.len()),
+        verified: included && circuit::FinalProof::of(record.mode, record.pv_stream.as_deref(), record.evm_inputs.as_ref())
+            .verified(&record.id, &version, || evm::setup(work_dir, &version.program_hash)),
+        program: ProgramInfo::from(&version),
+    })
+}
+
+/// The Merkle root and per-certificate verdicts a batch guest committed
+pub fn decode_batch_output(pv_stream: Option<&[u8]>) -> Result<BatchOutput> {
+    let public_values = pv_stream.ok_or_else(|| anyhow::anyhow!("Missing public values in batch proof"))?;
+    Ok(bincode::deserialize(public_values)?)
+}
+
+fn decode_hash(value: &str) -> Result<[u8; 32]> {
+    hex::decode(value)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Expected a 32-byte hash, got {}", value))
+}
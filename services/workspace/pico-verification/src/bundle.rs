@@ -0,0 +1,261 @@
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use crate::circuit;
+use crate::evm::{self, Groth16Key};
+use crate::registry::{self, GuestKind};
+use crate::types::{
+    ComplianceInfo, EvmProofInputs, MaterialCertification, ProofId, ProofRecord, ProvingMode, Threshold,
+    VerificationResult,
+};
+
+/// Identifies the bundle layout; bump when the signed payload changes shape
+pub const BUNDLE_FORMAT: &str = "material-proof-bundle/v2";
+
+/// A proof plus everything needed to check it offline, signed by the issuing server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofBundle {
+    pub format: String,
+    pub payload: BundlePayload,
+    pub signature: BundleSignature,
+}
+
+/// The signed part of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundlePayload {
+    pub proof_id: ProofId,
+    pub mode: ProvingMode,
+    pub proved_at: String,
+    pub bundled_at: String,
+    /// SHA-256 of the guest ELF that produced the proof
+    pub program_hash: String,
+    /// Hex-encoded values committed by the guest
+    pub public_values: String,
+    /// Recursive proof of a full proof, as in the proof record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embed_proof: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evm_inputs: Option<EvmProofInputs>,
+    /// Verifying key of the EVM setup an EVM proof was made with, vouched for by the issuer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evm_key: Option<Groth16Key>,
+    /// Digest of the guest's verifying key as the issuer's prover reported it, for EVM proofs of
+    /// guests the registry publishes no key for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest_vkey: Option<String>,
+    pub standard: StandardProfile,
+    /// The certification with private property values removed
+    pub certification: MaterialCertification,
+}
+
+impl BundlePayload {
+    /// The proof as a record, with the redacted certification it was bundled with
+    pub fn record(&self) -> Result<ProofRecord> {
+        Ok(ProofRecord {
+            id: self.proof_id.clone(),
+            timestamp: self.proved_at.clone(),
+            mode: self.mode,
+            certification: self.certification.clone(),
+            program_hash: Some(self.program_hash.clone()),
+            pv_stream: Some(hex::decode(&self.public_values)?),
+            embed_proof: self.embed_proof.clone(),
+            evm_inputs: self.evm_inputs.clone(),
+            metrics: None,
+        })
+    }
+}
+
+/// The standard the certification was checked against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StandardProfile {
+    pub designation: String,
+    /// Thresholds of the public properties; private thresholds stay private
+    pub thresholds: BTreeMap<String, Threshold>,
+    pub clauses: Vec<ComplianceInfo>,
+    pub property_standards_mapping: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleSignature {
+    pub algorithm: String,
+    /// Hex-encoded Ed25519 public key of the issuer
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// The usual verification result, extended with what the bundle was checked against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleVerification {
+    #[serde(flatten)]
+    pub verification: VerificationResult,
+    /// The trusted issuer that signed the bundle
+    pub issuer: String,
+    /// Whether the program hash was checked against one given by the caller, rather than only
+    /// against the known guest versions
+    pub program_pinned: bool,
+}
+
+/// Key the server signs bundles with
+pub struct Issuer {
+    key: SigningKey,
+}
+
+impl Issuer {
+    /// Load the hex-encoded signing key at `path`, creating one on first use
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let seed: [u8; 32] = hex::decode(std::fs::read_to_string(path)?.trim())?
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Signing key in {:?} must be 32 bytes", path))?;
+            return Ok(Issuer { key: SigningKey::from_bytes(&seed) });
+        }
+
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_private(path, hex::encode(key.to_bytes()).as_bytes())
+            .with_context(|| format!("Failed to write signing key to {:?}", path))?;
+        tracing::info!("Generated bundle signing key {:?}", path);
+        Ok(Issuer { key })
+    }
+
+    pub fn public_key(&self) -> String {
+        hex::encode(self.key.verifying_key().to_bytes())
+    }
+
+    fn sign(&self, payload: &BundlePayload) -> Result<BundleSignature> {
+        Ok(BundleSignature {
+            algorithm: "ed25519".to_string(),
+            public_key: self.public_key(),
+            signature: hex::encode(self.key.sign(&canonical_bytes(payload)?).to_bytes()),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?.write_all(contents)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, contents)
+}
+
+/// Going through `Value` sorts every object key, so signer and verifier hash the same bytes
+fn canonical_bytes(payload: &BundlePayload) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(&serde_json::to_value(payload)?)?)
+}
+
+/// Build a signed bundle for a stored proof; EVM proofs carry the key of the setup in `work_dir`
+pub fn create_bundle(record: &ProofRecord, issuer: &Issuer, work_dir: &Path) -> Result<ProofBundle> {
+    let public_values = record.pv_stream.as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Proof {} has no public values", record.id))?;
+
+    // The content-addressed id commits to the guest, so a match proves which program ran
+    let version = registry::resolve(&record.id, record.program_hash.as_deref(), GuestKind::Certificate)?;
+    if circuit::content_hash(&record.certification, &version.program_hash, record.mode)? != record.id.as_str() {
+        anyhow::bail!(
+            "Cannot attest the guest program of proof {}: it was generated by another build or before proofs were content-addressed",
+            record.id
+        );
+    }
+
+    let evm_setup = match record.mode {
+        ProvingMode::Evm => Some(evm::setup(work_dir, &version.program_hash)?),
+        _ => None,
+    };
+    let cert = &record.certification;
+    let payload = BundlePayload {
+        proof_id: record.id.clone(),
+        mode: record.mode,
+        proved_at: record.timestamp.clone(),
+        bundled_at: chrono::Utc::now().to_rfc3339(),
+        program_hash: version.program_hash.clone(),
+        public_values: hex::encode(public_values),
+        embed_proof: record.embed_proof.clone(),
+        evm_inputs: record.evm_inputs.clone(),
+        guest_vkey: evm_setup.as_ref().and_then(|setup| setup.guest_vkey.clone()),
+        evm_key: evm_setup.map(|setup| setup.key),
+        standard: StandardProfile {
+            designation: cert.material.designation.clone(),
+            thresholds: cert.properties_tested.public_properties.iter()
+                .filter_map(|(name, prop)| Some((name.clone(), prop.threshold.clone()?)))
+                .collect(),
+            clauses: cert.compliance.clone(),
+            property_standards_mapping: cert.properties_tested.property_standards_mapping.clone(),
+        },
+        certification: cert.redacted(),
+    };
+
+    let signature = issuer.sign(&payload)?;
+    Ok(ProofBundle { format: BUNDLE_FORMAT.to_string(), payload, signature })
+}
+
+pub fn read_bundle(path: &Path) -> Result<ProofBundle> {
+    parse_bundle(&std::fs::read(path)?)
+}
+
+/// Parse a bundle as downloaded, e.g. from an upload
+pub fn parse_bundle(bytes: &[u8]) -> Result<ProofBundle> {
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+/// Check a bundle without contacting the issuing server.
+///
+/// The bundle must be signed by one of `trusted_issuers`, since the key it carries says nothing
+/// about who made it. The proof itself is checked against the verifying key of the guest named by
+/// the bundle, which must be `program_hash` if given and a known version either way.
+pub fn verify_bundle(bundle: &ProofBundle, trusted_issuers: &[String], program_hash: Option<&str>) -> Result<BundleVerification> {
+    if bundle.format != BUNDLE_FORMAT {
+        anyhow::bail!("Unsupported bundle format {:?}, expected {}", bundle.format, BUNDLE_FORMAT);
+    }
+    if bundle.signature.algorithm != "ed25519" {
+        anyhow::bail!("Unsupported signature algorithm {:?}", bundle.signature.algorithm);
+    }
+
+    if trusted_issuers.is_empty() {
+        anyhow::bail!("No trusted issuer keys are configured, so no bundle can be accepted");
+    }
+    if !trusted_issuers.iter().any(|key| key.trim().eq_ignore_ascii_case(&bundle.signature.public_key)) {
+        anyhow::bail!("Bundle was signed by {}, which is not a trusted issuer", bundle.signature.public_key);
+    }
+
+    // Signature over the payload by the embedded key, now known to be a trusted one
+    let public_key: [u8; 32] = hex::decode(&bundle.signature.public_key)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Issuer public key must be 32 bytes"))?;
+    let signature: [u8; 64] = hex::decode(&bundle.signature.signature)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Signature must be 64 bytes"))?;
+    VerifyingKey::from_bytes(&public_key)?
+        .verify(&canonical_bytes(&bundle.payload)?, &Signature::from_bytes(&signature))
+        .map_err(|_| anyhow::anyhow!("Bundle signature does not match its contents"))?;
+
+    if let Some(expected) = program_hash {
+        if !expected.trim().eq_ignore_ascii_case(&bundle.payload.program_hash) {
+            anyhow::bail!("Proof was generated by program {}, expected {}", bundle.payload.program_hash, expected.trim());
+        }
+    }
+    // Either way the program has to be a known version, which also says how to decode its output
+    let version = registry::resolve(&bundle.payload.proof_id, Some(&bundle.payload.program_hash), GuestKind::Certificate)?;
+
+    let record = bundle.payload.record()?;
+    let proof = circuit::FinalProof::of(record.mode, record.pv_stream.as_deref(), record.evm_inputs.as_ref());
+    let verified = proof.verified(&record.id, &version, || Ok(evm::EvmSetup {
+        key: bundle.payload.evm_key.clone().ok_or_else(|| anyhow::anyhow!("Bundle has no EVM verifying key"))?,
+        guest_vkey: bundle.payload.guest_vkey.clone(),
+    }));
+    let verification = circuit::verification_result(&record, &record.certification, &version, verified)?;
+
+    Ok(BundleVerification {
+        verification,
+        issuer: bundle.signature.public_key.clone(),
+        program_pinned: program_hash.is_some(),
+    })
+}
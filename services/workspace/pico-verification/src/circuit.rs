@@ -1,101 +1,364 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use material_verification_lib::batch::CheckedProperty;
+use material_verification_lib::ComplianceInput;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use pico_sdk::client::DefaultProverClient;
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use serde_json::{json, Value};
-use crate::types::{MaterialCertification, VerificationResult, PropertyResult, ComplianceSummary, ZkpInfo};
-use std::rc::Rc;
-use std::cell::RefCell;
-
-fn get_elf_path() -> Result<String> {
-    let possible_paths = [
-        // Check verification-app paths
-        "/project/verification-app/target/riscv32im-pico-zkvm-elf",
-        "/project/verification-app/elf/riscv32im-pico-zkvm-elf",
-        // Check verification-app/app paths (where we ran the build)
-        "/project/verification-app/app/target/riscv32im-pico-zkvm-elf",
-        "/project/verification-app/app/elf/riscv32im-pico-zkvm-elf",
-        // Check target directory paths
-        "/project/target/riscv32im-pico-zkvm-elf"
-    ];
-    
-    for path in possible_paths {
-        if std::path::Path::new(path).exists() {
-            return Ok(path.to_string());
-        }
-    }
+use crate::types::{MaterialCertification, VerificationResult, PropertyResult, PropertyValue, ComplianceSummary, ZkpInfo,
+    ProvingMode, ProofRecord, ProofMetrics, EvmProofInputs, PreflightReport, GeneratedProof, ProofId, ProgramInfo, REDACTED};
+use sha2::{Digest, Sha256};
+use crate::evm::{self, EVM_DIR, EVM_INPUTS};
+use crate::guest;
+use crate::registry::{self, GuestKind, GuestVersion, OutputLayout};
+use crate::metrics;
+use crate::store::{self, ProofStore};
+use std::sync::OnceLock;
+
+/// Guest ELF chosen in the configuration and its program hash, run instead of the embedded one
+static GUEST_OVERRIDE: OnceLock<(Vec<u8>, String)> = OnceLock::new();
+
+/// Run the guest at `path` instead of the embedded one; only the first call has any effect.
+/// Returns the program hash proofs will be tagged with from now on.
+pub fn use_guest_elf(path: &Path) -> Result<&'static str> {
+    let elf = std::fs::read(path).with_context(|| format!("Failed to read guest ELF {:?}", path))?;
+    let hash = program_hash(&elf);
+    let (_, hash) = GUEST_OVERRIDE.get_or_init(|| (elf, hash));
+    Ok(hash)
+}
+
+/// The guest program the prover runs
+pub fn guest_elf() -> &'static [u8] {
+    GUEST_OVERRIDE.get().map_or(guest::ELF, |(elf, _)| elf)
+}
+
+/// Hash of [`guest_elf`], which new proofs are tagged with and stored proofs must match
+pub fn guest_program_hash() -> &'static str {
+    GUEST_OVERRIDE.get().map_or(guest::PROGRAM_HASH, |(_, hash)| hash)
+}
+
+/// Hex SHA-256 of a guest ELF, identifying the program a proof was generated by
+pub fn program_hash(elf: &[u8]) -> String {
+    hex::encode(Sha256::digest(elf))
+}
+
+/// Bump when the record layout or guest input encoding changes in a way that invalidates old proofs
+const CONTENT_HASH_VERSION: &str = "material-proof-v1";
+
+/// Canonical hash identifying a proof of this certification by the guest with `program_hash` in this mode
+pub fn content_hash(cert: &MaterialCertification, program_hash: &str, mode: ProvingMode) -> Result<String> {
+    // Going through `Value` sorts every object key, so map ordering cannot change the hash
+    let canonical = serde_json::to_vec(&serde_json::to_value(cert)?)?;
     
-    Err(anyhow::anyhow!("Could not find Pico ELF file in any expected location"))
+    let mut hasher = Sha256::new();
+    hasher.update(CONTENT_HASH_VERSION.as_bytes());
+    hasher.update(hex::decode(program_hash)?);
+    hasher.update(mode.label().as_bytes());
+    hasher.update(cert.material.designation.as_bytes());
+    hasher.update(&canonical);
+    Ok(hex::encode(hasher.finalize()))
 }
 
-/// Create a verification circuit based on material certification data
-pub fn create_verification_circuit(cert: &MaterialCertification) -> Result<()> {
-    // We're not dynamically creating circuits here, 
-    // just using the pre-compiled ELF
-    Ok(())
+/// Whether the proof in `record` covers `cert`.
+///
+/// The guest commits only verdicts, so what ties a proof to a certification is its id: content
+/// addressed ids commit to the full certification, private values included, and a record whose
+/// certification was swapped keeps its id and fails here. Records from before program hashes
+/// were recorded have no such id and can only be compared with the certification they carry.
+pub fn covers(record: &ProofRecord, cert: &MaterialCertification) -> Result<bool> {
+    match &record.program_hash {
+        Some(program_hash) => Ok(content_hash(cert, program_hash, record.mode)? == record.id.as_str()),
+        None => Ok(serde_json::to_value(cert)? == serde_json::to_value(&record.certification)?),
+    }
 }
 
-/// Generate a zero-knowledge proof using Pico
-pub fn generate_proof(circuit: &(), output_dir: &Path, cert: &MaterialCertification) -> Result<String> {
+/// Create a prover client with the certification already written to the guest's stdin
+fn prepare_client(elf: &[u8], cert: &MaterialCertification) -> Result<DefaultProverClient> {
     // Initialize the Pico prover client
-    let elf_path = get_elf_path()?;
-    let elf = std::fs::read(elf_path)?;
-    let client = DefaultProverClient::new(&elf);
+    let client = DefaultProverClient::new(elf);
     let stdin_builder = client.get_stdin_builder();
     
     // Prepare input for the RISC-V program
-    let (property_values, min_thresholds, max_thresholds, private_indices) = prepare_material_data(cert);
-    
-    let input = json!({
-        "property_values": property_values,
-        "min_thresholds": min_thresholds,
-        "max_thresholds": max_thresholds,
-        "private_indices": private_indices
-    });
+    let input = ComplianceInput {
+        properties: checked_properties(cert).into_iter().map(|(property, _)| property).collect(),
+    };
     
     // Write input to the VM
     stdin_builder.borrow_mut().write(&input);
     
-    // Generate proof
-    let proof = client.prove_fast()?;
+    Ok(client)
+}
+
+/// Run the guest in the emulator only, without proving, to check inputs and size the job
+pub fn preflight(cert: &MaterialCertification, mode: ProvingMode) -> Result<PreflightReport> {
+    let client = prepare_client(guest_elf(), cert)?;
+    let stdin = client.get_stdin_builder().borrow().clone();
     
-    // Generate a unique ID for this proof
-    let proof_id = uuid::Uuid::new_v4().to_string();
+    let started = Instant::now();
+    let (cycles, public_values) = client.emulate(stdin);
+    let emulation_ms = started.elapsed().as_millis() as u64;
     
-    // Save the full proof data to a file
-    let proof_path = output_dir.join(format!("{}.json", proof_id));
-    let proof_data = json!({
-        "id": proof_id,
-        "timestamp": chrono::Utc::now().to_rfc3339(),
-        "certification": cert,
-        "pv_stream": proof.pv_stream  // Store the actual proof data
-    });
+    let (all_compliant, property_compliance) = decode_public_values(&public_values)?;
+    let properties = process_properties(cert, &property_compliance);
+    let (estimated_proving_seconds, estimated_memory_mb) = estimate_proving_cost(cycles, mode);
     
-    std::fs::write(&proof_path, serde_json::to_string_pretty(&proof_data)?)?;
+    Ok(PreflightReport {
+        material_id: cert.certificate_id.clone(),
+        mode,
+        overall_compliance: if all_compliant { "PASS".to_string() } else { "FAIL".to_string() },
+        properties,
+        cycles,
+        emulation_ms,
+        estimated_proving_seconds,
+        estimated_memory_mb,
+    })
+}
+
+/// Rough proving cost for a cycle count, from throughput measured on the reference prover host
+fn estimate_proving_cost(cycles: u64, mode: ProvingMode) -> (f64, u64) {
+    // (cycles per second, fixed seconds, base memory MB, memory MB per million cycles)
+    let (throughput, fixed_seconds, base_memory_mb, memory_per_mcycle) = match mode {
+        ProvingMode::Fast => (1_500_000.0, 2.0, 2_048, 64),
+        ProvingMode::Full => (400_000.0, 45.0, 8_192, 128),
+        ProvingMode::Evm => (400_000.0, 165.0, 16_384, 128),
+    };
     
-    // Return just the proof ID
-    Ok(proof_id)
+    let seconds = fixed_seconds + cycles as f64 / throughput;
+    let memory_mb = base_memory_mb + memory_per_mcycle * cycles.div_ceil(1_000_000);
+    (seconds, memory_mb)
 }
 
-/// Verify a proof and generate verification results
-pub fn verify_proof(proof_path: &str, output_dir: &Path, cert: &MaterialCertification) -> Result<VerificationResult> {
-    // Load the proof
-    let proof_data = std::fs::read_to_string(proof_path)?;
-    let proof: Value = serde_json::from_str(&proof_data)?;
+/// Generate a zero-knowledge proof using Pico, reusing an identical earlier proof unless `force` is set.
+/// The record goes to `store`; proving artifacts are kept in `work_dir`.
+pub fn generate_proof(store: &dyn ProofStore, work_dir: &Path, cert: &MaterialCertification, mode: ProvingMode, force: bool) -> Result<GeneratedProof> {
+    let started = Instant::now();
+    let elf = guest_elf();
     
-    // Extract the proof stream from the proof file - this is the key part we need to fix
-    let public_values = proof["pv_stream"].as_array()
-        .ok_or_else(|| anyhow::anyhow!("Missing public values in proof"))?;
+    if !force {
+        if let Some(existing) = existing_proof(store, cert, mode)? {
+            return Ok(existing);
+        }
+    }
+    let proof_id: ProofId = content_hash(cert, guest_program_hash(), mode)?.parse()?;
+    
+    store::track_job(store, &proof_id, || {
+        prove_and_store(elf, store, work_dir, cert, &proof_id, mode, started)
+    })?;
+    
+    metrics::PROVING_DURATION
+        .with_label_values(&[mode.label()])
+        .observe(started.elapsed().as_secs_f64());
+    
+    Ok(GeneratedProof { proof_id, reused: false })
+}
+
+/// The stored proof of `cert` in `mode` by the running guest, if there is one.
+///
+/// Proofs are named by their content hash, so an identical request maps to the same record.
+pub fn existing_proof(store: &dyn ProofStore, cert: &MaterialCertification, mode: ProvingMode) -> Result<Option<GeneratedProof>> {
+    let proof_id: ProofId = content_hash(cert, guest_program_hash(), mode)?.parse()?;
+    if store.get_proof(&proof_id)?.is_none() {
+        return Ok(None);
+    }
+    tracing::info!("Reusing existing proof {} for {}", proof_id, cert.certificate_id);
+    Ok(Some(GeneratedProof { proof_id, reused: true }))
+}
+
+fn prove_and_store(elf: &[u8], store: &dyn ProofStore, work_dir: &Path, cert: &MaterialCertification, proof_id: &ProofId, mode: ProvingMode, started: Instant) -> Result<()> {
+    let client = prepare_client(elf, cert).inspect_err(|_| metrics::record_failure("setup"))?;
+    let setup_ms = started.elapsed().as_millis() as u64;
+    
+    // Emulate first to record the cycle count; this is cheap next to proving
+    let phase = Instant::now();
+    let stdin = client.get_stdin_builder().borrow().clone();
+    let (cycles, _) = client.emulate(stdin);
+    let emulation_ms = phase.elapsed().as_millis() as u64;
+    
+    // Generate proof in the requested mode
+    tracing::info!("Proving {} in {:?} mode ({} cycles)", cert.certificate_id, mode, cycles);
+    let phase = Instant::now();
+    let ProverOutput { pv_stream, embed_proof, evm_inputs } = prove(&client, store, work_dir, guest_program_hash(), proof_id, mode)
+        .inspect_err(|_| metrics::record_failure("proving"))?;
+    let proving_ms = phase.elapsed().as_millis() as u64;
+    
+    // Everything that has to be kept to present the proof later
+    let proof_size_bytes = pv_stream.as_ref().map_or(0, |pv| pv.len() as u64)
+        + embed_proof.as_ref().map_or(0, |proof| proof.len() as u64)
+        + evm_inputs.as_ref().map_or(Ok(0), |inputs| serde_json::to_vec(inputs).map(|v| v.len() as u64))?
+        + dir_size(&proof_id.artifacts_dir(work_dir));
     
-    // The first public value is the overall compliance status
-    let all_compliant = public_values[0].as_u64().unwrap_or(0) > 0;
+    // Save the full proof data to a file
+    let record = ProofRecord {
+        id: proof_id.clone(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        mode,
+        certification: cert.clone(),
+        program_hash: Some(guest_program_hash().to_string()),
+        pv_stream,  // Store the actual proof data
+        embed_proof,
+        evm_inputs,
+        metrics: Some(ProofMetrics {
+            cycles,
+            setup_ms,
+            emulation_ms,
+            proving_ms,
+            peak_memory_bytes: metrics::peak_memory_bytes(),
+            proof_size_bytes,
+        }),
+    };
     
-    // Process the rest of public values
-    let mut property_compliance = Vec::new();
-    for i in 1..public_values.len() {
-        property_compliance.push(public_values[i].as_u64().unwrap_or(0) > 0);
+    store.put_proof(&record)
+        .inspect_err(|_| metrics::record_failure("persist"))
+}
+
+/// What a proving run leaves to be stored with the record
+pub(crate) struct ProverOutput {
+    pub pv_stream: Option<Vec<u8>>,
+    /// Base64 bincode of the recursive proof, for full proofs
+    pub embed_proof: Option<String>,
+    pub evm_inputs: Option<EvmProofInputs>,
+}
+
+/// Run the prover for the guest with `program_hash` in the requested mode and return the public
+/// values plus whatever proves them
+pub(crate) fn prove(client: &DefaultProverClient, store: &dyn ProofStore, output_dir: &Path, program_hash: &str, proof_id: &ProofId, mode: ProvingMode) -> Result<ProverOutput> {
+    match mode {
+        ProvingMode::Fast => Ok(ProverOutput { pv_stream: client.prove_fast()?.pv_stream, embed_proof: None, evm_inputs: None }),
+        ProvingMode::Full => {
+            // Keep the recursion artifacts next to the proof record
+            let artifacts_dir = proof_id.artifacts_dir(output_dir);
+            std::fs::create_dir_all(&artifacts_dir)?;
+            let (riscv_proof, embed_proof) = client.prove(artifacts_dir)?;
+            share_artifacts(store, output_dir, proof_id.as_str());
+            Ok(ProverOutput {
+                pv_stream: riscv_proof.pv_stream,
+                embed_proof: Some(BASE64.encode(bincode::serialize(&embed_proof)?)),
+                evm_inputs: None,
+            })
+        },
+        ProvingMode::Evm => {
+            // The Groth16 setup is shared between proofs and only generated once. The prover
+            // reads it from and writes its outputs to fixed names in the same directory, so
+            // one EVM proof runs at a time and copies its inputs out before letting go.
+            // Another instance sharing the store may already have made the setup.
+            let evm_dir = output_dir.join(EVM_DIR);
+            let _lock = evm::lock_setup(&evm_dir)?;
+            let need_setup = !evm::setup_ready(store, output_dir);
+            client.prove_evm(need_setup, evm_dir.clone(), "kb")?;
+            
+            let artifacts_dir = proof_id.artifacts_dir(output_dir);
+            std::fs::create_dir_all(&artifacts_dir)?;
+            std::fs::copy(evm_dir.join(EVM_INPUTS), artifacts_dir.join(EVM_INPUTS))?;
+            share_artifacts(store, output_dir, proof_id.as_str());
+            let inputs: EvmProofInputs = serde_json::from_str(&std::fs::read_to_string(artifacts_dir.join(EVM_INPUTS))?)?;
+            
+            // The SDK reports the guest's verifying key only with a proof; later proofs of this
+            // guest are checked against what it said here
+            let new_guest = evm::record_guest_vkey(&evm_dir, program_hash, &inputs.riscv_v_key)?;
+            if need_setup || new_guest {
+                share_artifacts(store, output_dir, EVM_DIR);
+            }
+            let public_values = hex::decode(inputs.public_values.trim_start_matches("0x"))?;
+            Ok(ProverOutput { pv_stream: Some(public_values), embed_proof: None, evm_inputs: Some(inputs) })
+        },
+    }
+}
+
+/// Hand artifacts to the store for other instances; they only lose a shortcut if this fails
+fn share_artifacts(store: &dyn ProofStore, output_dir: &Path, name: &str) {
+    if let Err(err) = store.put_artifacts(output_dir, name) {
+        tracing::warn!("Failed to share proving artifacts {}: {:#}", name, err);
     }
+}
+
+/// Total size of the files directly inside a directory, or 0 if it does not exist
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok()?.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum())
+        .unwrap_or(0)
+}
+
+/// Verify a stored proof and generate verification results. EVM proofs are checked against the
+/// setup in `work_dir`, where they were made.
+pub fn verify_proof(record: &ProofRecord, cert: &MaterialCertification, work_dir: &Path) -> Result<VerificationResult> {
+    let version = registry::resolve(&record.id, record.program_hash.as_deref(), GuestKind::Certificate)?;
+    let proof = FinalProof::of(record.mode, record.pv_stream.as_deref(), record.evm_inputs.as_ref());
+    let verified = proof.verified(&record.id, &version, || evm::setup(work_dir, &version.program_hash));
+    verification_result(record, cert, &version, verified)
+}
+
+/// What makes a proof final, checked against the verifying key of the guest it names rather
+/// than taken from the mode its record declares
+pub enum FinalProof<'a> {
+    /// Fast proofs are not succinct and cannot be checked on their own
+    None,
+    /// The prover checks recursive proofs as it makes them, but the Pico SDK has no verifier to
+    /// check one again later, so they cannot be shown to be final after the fact
+    Full,
+    Evm { public_values: &'a [u8], inputs: Option<&'a EvmProofInputs> },
+}
+
+impl<'a> FinalProof<'a> {
+    pub fn of(mode: ProvingMode, public_values: Option<&'a [u8]>, evm_inputs: Option<&'a EvmProofInputs>) -> Self {
+        match mode {
+            ProvingMode::Fast => FinalProof::None,
+            ProvingMode::Full => FinalProof::Full,
+            ProvingMode::Evm => FinalProof::Evm { public_values: public_values.unwrap_or_default(), inputs: evm_inputs },
+        }
+    }
+
+    /// Check the proof, with `evm_setup` giving the EVM setup it was made with.
+    ///
+    /// EVM proofs are checked against the published verifying key of the guest if the registry has
+    /// one, and otherwise against the one the prover reported when it proved the guest with the setup.
+    pub fn check(&self, version: &GuestVersion, evm_setup: impl FnOnce() -> Result<evm::EvmSetup>) -> Result<()> {
+        match self {
+            FinalProof::None => anyhow::bail!("Fast proofs are not final proofs"),
+            FinalProof::Full => anyhow::bail!("Recursive proofs can only be checked while they are made; prove in EVM mode for a proof that can be checked later"),
+            FinalProof::Evm { public_values, inputs } => {
+                let inputs = inputs.ok_or_else(|| anyhow::anyhow!("The EVM proof inputs were not kept"))?;
+                if hex::decode(inputs.public_values.trim_start_matches("0x"))? != *public_values {
+                    anyhow::bail!("The EVM proof commits to other public values");
+                }
+                let setup = evm_setup()?;
+                let riscv_vkey = version.verifying_key.clone().or(setup.guest_vkey)
+                    .ok_or_else(|| anyhow::anyhow!("No verifying key is known for guest {}", version.version))?;
+                evm::verify_groth16(&setup.key, inputs, &riscv_vkey)
+            },
+        }
+    }
+
+    /// Whether [`check`](Self::check) passes, logging why not
+    pub fn verified(&self, id: &ProofId, version: &GuestVersion, evm_setup: impl FnOnce() -> Result<evm::EvmSetup>) -> bool {
+        match self.check(version, evm_setup) {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::warn!("Proof {} is not a verified final proof: {:#}", id, err);
+                false
+            },
+        }
+    }
+}
+
+/// Decode a proof's public values into verification results with the decoder of its guest
+/// version; `verified` says whether its final proof was checked
+pub fn verification_result(record: &ProofRecord, cert: &MaterialCertification, version: &GuestVersion, verified: bool) -> Result<VerificationResult> {
+    // Extract the proof stream from the proof record
+    let public_values = record.pv_stream.as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Missing public values in proof"))?;
+    let (all_compliant, property_compliance) = match version.output_layout {
+        // Only the overall verdict of the original guest can be read
+        OutputLayout::ComplianceV0 => (decode_public_values(public_values)?.0, Vec::new()),
+        OutputLayout::ComplianceV1 => decode_public_values(public_values)?,
+        OutputLayout::BatchV1 => anyhow::bail!("Guest {} proves batches, not single certificates", version.version),
+    };
     
     // Generate timestamp and ID
     let now = SystemTime::now()
@@ -131,11 +394,12 @@ pub fn verify_proof(proof_path: &str, output_dir: &Path, cert: &MaterialCertific
         },
         zkp_info: ZkpInfo {
             implemented: true,
-            proof_type: "Pico zkVM Proof".to_string(),
+            proof_type: record.mode.proof_type().to_string(),
             circuit: "material_verifier_zkvm".to_string(),
-            proof: proof_path.to_string(),
+            proof: record.id.to_string(),
             public_signals: serde_json::to_string(&public_values)?,
-            verified: true,
+            verified,
+            program: Some(ProgramInfo::from(version)),
         },
     };
     
@@ -143,87 +407,78 @@ pub fn verify_proof(proof_path: &str, output_dir: &Path, cert: &MaterialCertific
 }
 
 // Helper functions
-fn prepare_material_data(cert: &MaterialCertification) -> (Vec<f64>, Vec<f64>, Vec<f64>, Vec<usize>) {
-    let mut property_values = Vec::new();
-    let mut min_thresholds = Vec::new();
-    let mut max_thresholds = Vec::new();
-    let mut private_indices = Vec::new();
-    
-    // Process public properties
-    for (idx, (name, prop)) in cert.properties_tested.public_properties.iter().enumerate() {
-        if let Value::Number(num) = &prop.value {
-            if let Some(val) = num.as_f64() {
-                property_values.push(val);
-                
-                if let Some(threshold) = &prop.threshold {
-                    min_thresholds.push(threshold.min);
-                    max_thresholds.push(threshold.max);
-                } else {
-                    min_thresholds.push(f64::NEG_INFINITY);
-                    max_thresholds.push(f64::INFINITY);
-                }
-            }
-        }
-    }
+
+/// Split the guest's committed values into overall and per-property compliance
+fn decode_public_values(public_values: &[u8]) -> Result<(bool, Vec<bool>)> {
+    let (overall, properties) = public_values.split_first()
+        .ok_or_else(|| anyhow::anyhow!("Missing public values in proof"))?;
     
-    // Process private properties
-    let public_count = property_values.len();
-    for (idx, (name, prop)) in cert.properties_tested.private_properties.iter().enumerate() {
-        if let Value::Number(num) = &prop.value {
-            if let Some(val) = num.as_f64() {
-                property_values.push(val);
-                
-                if let Some(threshold) = &prop.threshold {
-                    min_thresholds.push(threshold.min);
-                    max_thresholds.push(threshold.max);
-                } else {
-                    min_thresholds.push(f64::NEG_INFINITY);
-                    max_thresholds.push(f64::INFINITY);
-                }
-                
-                // Add this index to private indices
-                private_indices.push(public_count + idx);
-            }
-        }
+    // The first public value is the overall compliance status, the rest are per property
+    Ok((*overall > 0, properties.iter().map(|v| *v > 0).collect()))
+}
+
+/// Every property by whether it is private, public then private and each sorted by name
+fn ordered_properties(cert: &MaterialCertification) -> Vec<(&String, &PropertyValue, bool)> {
+    let mut properties = Vec::new();
+    let tested = &cert.properties_tested;
+    for (group, is_private) in [(&tested.public_properties, false), (&tested.private_properties, true)] {
+        let mut names: Vec<&String> = group.keys().collect();
+        names.sort();
+        properties.extend(names.into_iter().map(|name| (name, &group[name], is_private)));
     }
-    
-    (property_values, min_thresholds, max_thresholds, private_indices)
+    properties
 }
 
-fn process_properties(cert: &MaterialCertification, compliance_results: &[bool]) -> HashMap<String, PropertyResult> {
-    let mut results = HashMap::new();
-    let mut compliance_idx = 0;
-    
-    // Process public properties
-    for (name, prop) in &cert.properties_tested.public_properties {
-        if compliance_idx < compliance_results.len() {
-            results.insert(name.clone(), PropertyResult {
-                property: name.clone(),
-                value: prop.value.clone(),
-                thresholds: json!({
-                    "min": prop.threshold.as_ref().map(|t| t.min).unwrap_or(f64::NEG_INFINITY),
-                    "max": prop.threshold.as_ref().map(|t| t.max).unwrap_or(f64::INFINITY)
-                }),
-                compliant: compliance_results[compliance_idx],
-                is_private: false,
-            });
-            compliance_idx += 1;
-        }
+/// The numeric properties the guest checks and whether each is private, public then private and
+/// each sorted by name. The guest commits one verdict per property in this order.
+pub(crate) fn checked_properties(cert: &MaterialCertification) -> Vec<(CheckedProperty, bool)> {
+    ordered_properties(cert).into_iter()
+        .filter_map(|(name, prop, is_private)| Some((checked_property(name, prop)?, is_private)))
+        .collect()
+}
+
+/// The properties the guest committed a verdict for, in the same order as [`checked_properties`],
+/// also counting private values that were [redacted](MaterialCertification::redacted)
+fn verdict_order(cert: &MaterialCertification) -> Vec<(&String, &PropertyValue, bool)> {
+    ordered_properties(cert).into_iter()
+        .filter(|(_, prop, _)| prop.value.is_number() || prop.value == REDACTED)
+        .collect()
+}
+
+/// The range a property must lie in, unbounded on the sides without a threshold
+fn bounds(prop: &PropertyValue) -> (f64, f64) {
+    match &prop.threshold {
+        Some(threshold) => (threshold.min, threshold.max),
+        None => (f64::NEG_INFINITY, f64::INFINITY),
     }
-    
-    // Process private properties
-    for (name, prop) in &cert.properties_tested.private_properties {
-        if compliance_idx < compliance_results.len() {
-            results.insert(name.clone(), PropertyResult {
+}
+
+fn checked_property(name: &str, prop: &PropertyValue) -> Option<CheckedProperty> {
+    let value = match &prop.value {
+        Value::Number(num) => num.as_f64()?,
+        _ => return None,
+    };
+    let (min, max) = bounds(prop);
+
+    Some(CheckedProperty { name: name.to_string(), value, min, max })
+}
+
+fn process_properties(cert: &MaterialCertification, compliance_results: &[bool]) -> HashMap<String, PropertyResult> {
+    verdict_order(cert).into_iter().zip(compliance_results)
+        .map(|((name, prop, is_private), &compliant)| {
+            let (value, thresholds) = if is_private {
+                (json!(REDACTED), json!(REDACTED))
+            } else {
+                let (min, max) = bounds(prop);
+                (prop.value.clone(), json!({ "min": min, "max": max }))
+            };
+            (name.clone(), PropertyResult {
                 property: name.clone(),
-                value: json!("**REDACTED**"),
-                thresholds: json!("**REDACTED**"),
-                compliant: compliance_results[compliance_idx],
-                is_private: true,
-            });
-            compliance_idx += 1;
-        }
-    }
-    
-    results
-}
\ No newline at end of file
+                value,
+                thresholds,
+                compliant,
+                is_private,
+            })
+        })
+        .collect()
+}
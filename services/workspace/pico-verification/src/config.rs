@@ -0,0 +1,325 @@
+//! Settings for the server and the CLI.
+//!
+//! Built-in defaults are overlaid by a TOML file, which is in turn overlaid by `PICO_*`
+//! environment variables and command line flags (see `main.rs`). The result is checked
+//! once at startup, so a bad setting stops the process instead of the first request.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use crate::registry::GuestVersion;
+use crate::store::{StoreBackend, StoreConfig};
+use crate::tenant::Quota;
+use crate::types::OrgId;
+
+/// Everything that can be set in the configuration file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where proofs, keys and logs are kept unless a setting says otherwise
+    pub output_dir: Option<PathBuf>,
+    pub server: ServerConfig,
+    pub guest: GuestConfig,
+    pub store: StoreConfig,
+    pub auth: AuthConfig,
+    pub bundles: BundleConfig,
+    /// Proving limits, keyed by organisation; organisations not listed are unlimited
+    pub quotas: HashMap<OrgId, Quota>,
+    pub workers: WorkerConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address to listen on
+    pub address: IpAddr,
+    pub port: u16,
+    /// Listen on this Unix domain socket instead of a TCP port, e.g. behind a reverse proxy
+    pub unix_socket: Option<PathBuf>,
+    /// Serve HTTPS instead of plain HTTP
+    pub tls: Option<TlsConfig>,
+    pub cors: CorsConfig,
+    /// Key proof bundles are signed with, created on first use (defaults to `<output>/issuer.key`)
+    pub signing_key: Option<PathBuf>,
+    /// Also serve `/metrics` here without authentication, for a scraper on a private network;
+    /// on the API itself it needs an admin
+    pub metrics_address: Option<SocketAddr>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 3000,
+            unix_socket: None,
+            tls: None,
+            cors: CorsConfig::default(),
+            signing_key: None,
+            metrics_address: None,
+        }
+    }
+}
+
+/// PEM files for HTTPS; replacing them takes effect without a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Certificate chain, leaf first
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Which browser pages may call the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins browsers may call the API from; any origin when empty
+    pub origins: Vec<String>,
+    pub methods: Vec<String>,
+    pub headers: Vec<String>,
+    /// Let browsers send cookies along; needs explicit origins
+    pub allow_credentials: bool,
+    /// Seconds browsers may cache a preflight response
+    pub max_age: Option<u64>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            origins: Vec::new(),
+            methods: ["GET", "POST", "DELETE", "OPTIONS"].map(String::from).to_vec(),
+            headers: ["Content-Type", "Authorization", "X-API-Key"].map(String::from).to_vec(),
+            allow_credentials: false,
+            max_age: None,
+        }
+    }
+}
+
+/// Guest programs to run instead of the ones built into the binary, for guest development.
+/// Proofs are tagged with their program hash, so other builds will not accept them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuestConfig {
+    pub elf: Option<PathBuf>,
+    pub batch_elf: Option<PathBuf>,
+    /// Earlier guest releases whose proofs are still accepted
+    pub versions: Vec<GuestVersion>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// JSON file of API keys and their roles (defaults to `<output>/api-keys.json` if present)
+    pub api_keys: Option<PathBuf>,
+    /// Shared secret for HS256 bearer tokens
+    pub jwt_secret: Option<String>,
+    /// Treat every caller as an admin; for local development only
+    pub insecure_no_auth: bool,
+    /// Append-only log of access decisions (defaults to `<output>/audit.jsonl`)
+    pub audit_log: Option<PathBuf>,
+}
+
+/// Who proof bundles are accepted from
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BundleConfig {
+    /// Hex-encoded Ed25519 public keys of other servers whose bundles are accepted, on top of
+    /// this server's own signing key
+    pub trusted_issuers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkerConfig {
+    /// Runtime worker threads; one per CPU when unset
+    pub threads: Option<usize>,
+    /// Proofs generated at the same time, the rest waiting in the queue; unlimited in the API and
+    /// one at a time for CLI shipments when unset
+    pub max_concurrent_proofs: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log shippers
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// A tracing filter such as `info` or `material_pico_verify=debug,warp=info`
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig { level: "info".to_string(), format: LogFormat::Text }
+    }
+}
+
+const REDACTED: &str = "<redacted>";
+
+impl Config {
+    /// Read a TOML configuration file; settings it leaves out keep their defaults
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read configuration file {:?}", path))?;
+        toml::from_str(&content).with_context(|| format!("Invalid configuration file {:?}", path))
+    }
+
+    /// The output directory; only call after [`Config::validate`]
+    pub fn output_dir(&self) -> &Path {
+        self.output_dir.as_deref().expect("output_dir is checked by validate")
+    }
+
+    /// Check settings every command relies on, reporting all problems at once
+    pub fn validate(&self) -> Result<()> {
+        report(self.problems())
+    }
+
+    /// Check everything needed to serve the API, on top of [`Config::validate`]
+    pub fn validate_server(&self) -> Result<()> {
+        let mut problems = self.problems();
+
+        let server = &self.server;
+        let cors = &server.cors;
+        for origin in &cors.origins {
+            let valid = origin.parse::<warp::http::Uri>().is_ok_and(|uri| {
+                matches!(uri.scheme_str(), Some("http" | "https"))
+                    && uri.authority().is_some()
+                    && uri.path_and_query().is_none_or(|path| path.as_str().is_empty() || path.as_str() == "/")
+            });
+            if !valid {
+                problems.push(format!("server.cors.origins: {:?} is not an origin like https://example.com", origin));
+            }
+        }
+        for method in &cors.methods {
+            if warp::http::Method::from_bytes(method.as_bytes()).is_err() {
+                problems.push(format!("server.cors.methods: {:?} is not an HTTP method", method));
+            }
+        }
+        for header in &cors.headers {
+            if warp::http::header::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                problems.push(format!("server.cors.headers: {:?} is not a header name", header));
+            }
+        }
+        if cors.allow_credentials && cors.origins.is_empty() {
+            problems.push("server.cors.allow_credentials needs explicit server.cors.origins".to_string());
+        }
+        if let Some(tls) = &server.tls {
+            if server.unix_socket.is_some() {
+                problems.push("server.tls cannot be used with server.unix_socket; terminate TLS at the proxy".to_string());
+            }
+            for (name, path) in [("server.tls.cert", &tls.cert), ("server.tls.key", &tls.key)] {
+                if !path.is_file() {
+                    problems.push(format!("{}: {:?} does not exist", name, path));
+                }
+            }
+        }
+        if let Some(dir) = server.unix_socket.as_ref().and_then(|path| path.parent()) {
+            if !dir.as_os_str().is_empty() && !dir.is_dir() {
+                problems.push(format!("server.unix_socket: directory {:?} does not exist", dir));
+            }
+        }
+
+        let auth = &self.auth;
+        if auth.insecure_no_auth && (auth.api_keys.is_some() || auth.jwt_secret.is_some()) {
+            problems.push("auth.insecure_no_auth cannot be combined with api_keys or jwt_secret".to_string());
+        }
+        let default_keys = self.output_dir.as_ref().map(|dir| dir.join("api-keys.json")).filter(|path| path.exists());
+        if !auth.insecure_no_auth && auth.api_keys.is_none() && default_keys.is_none() && auth.jwt_secret.is_none() {
+            problems.push("auth: set api_keys or jwt_secret, or insecure_no_auth for local development".to_string());
+        }
+        if let Some(path) = &auth.api_keys {
+            if !path.is_file() {
+                problems.push(format!("auth.api_keys: {:?} does not exist", path));
+            }
+        }
+
+        report(problems)
+    }
+
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.output_dir.is_none() {
+            problems.push("output_dir is required".to_string());
+        }
+        for (name, path) in [("guest.elf", &self.guest.elf), ("guest.batch_elf", &self.guest.batch_elf)] {
+            if let Some(path) = path {
+                if !path.is_file() {
+                    problems.push(format!("{}: {:?} does not exist", name, path));
+                }
+            }
+        }
+        for (index, version) in self.guest.versions.iter().enumerate() {
+            let hash = &version.program_hash;
+            if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+                problems.push(format!("guest.versions[{}]: program_hash must be 64 lowercase hex digits", index));
+            }
+            if self.guest.versions[..index].iter().any(|earlier| earlier.program_hash == *hash) {
+                problems.push(format!("guest.versions[{}]: program {} is listed twice", index, hash));
+            }
+            let kind = version.output_layout.kind();
+            if version.untagged && self.guest.versions[..index].iter().any(|earlier| earlier.untagged && earlier.output_layout.kind() == kind) {
+                problems.push(format!("guest.versions[{}]: only one {} guest can be untagged", index, kind.label()));
+            }
+        }
+        for (index, key) in self.bundles.trusted_issuers.iter().enumerate() {
+            if key.len() != 64 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+                problems.push(format!("bundles.trusted_issuers[{}]: must be a 64 hex digit public key", index));
+            }
+        }
+        if let Some(s3) = &self.store.s3 {
+            if s3.endpoint.is_empty() || s3.bucket.is_empty() {
+                problems.push("store.s3 needs both an endpoint and a bucket".to_string());
+            }
+            if s3.access_key.is_some() != s3.secret_key.is_some() {
+                problems.push("store.s3 needs both access_key and secret_key, or neither".to_string());
+            }
+        }
+        if self.store.backend == StoreBackend::Sqlite && self.store.path.as_ref().is_some_and(|path| path.is_dir()) {
+            problems.push("store.path must be a database file for the sqlite backend".to_string());
+        }
+        if self.workers.threads == Some(0) {
+            problems.push("workers.threads must be at least 1".to_string());
+        }
+        if self.workers.max_concurrent_proofs == Some(0) {
+            problems.push("workers.max_concurrent_proofs must be at least 1".to_string());
+        }
+        if let Err(err) = tracing_subscriber::EnvFilter::try_new(&self.log.level) {
+            problems.push(format!("log.level: {}", err));
+        }
+
+        problems
+    }
+
+    /// The effective configuration as TOML, with secrets masked
+    pub fn to_redacted_toml(&self) -> Result<String> {
+        let mut config = self.clone();
+        if config.auth.jwt_secret.is_some() {
+            config.auth.jwt_secret = Some(REDACTED.to_string());
+        }
+        if let Some(s3) = &mut config.store.s3 {
+            if s3.secret_key.is_some() {
+                s3.secret_key = Some(REDACTED.to_string());
+            }
+        }
+        Ok(toml::to_string_pretty(&config)?)
+    }
+}
+
+fn report(problems: Vec<String>) -> Result<()> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+    anyhow::bail!("Invalid configuration:\n  {}", problems.join("\n  "))
+}
@@ -0,0 +1,190 @@
+use serde::Serialize;
+use std::convert::Infallible;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+use crate::validation::FieldError;
+
+/// Every way an API request can fail, each with a stable machine-readable code
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Authentication required: {0}")]
+    Unauthorized(String),
+
+    #[error("Not allowed: {0}")]
+    Forbidden(String),
+
+    #[error("Proof {0} not found")]
+    ProofNotFound(String),
+
+    #[error("Batch {0} not found")]
+    BatchNotFound(String),
+
+    #[error("No schema named {0}")]
+    SchemaNotFound(String),
+
+    #[error("Certificate {certificate_id} is not part of batch {batch_id}")]
+    CertificateNotInBatch { batch_id: String, certificate_id: String },
+
+    #[error("Proof {0} cannot be read from storage")]
+    ProofUnreadable(String),
+
+    #[error("Proof {0} is stored in an invalid format")]
+    ProofCorrupt(String),
+
+    #[error("Proof {proof_id} was generated in {mode} mode; {required} is required")]
+    WrongProofMode { proof_id: String, mode: String, required: String },
+
+    #[error("Bundle cannot be issued: {0}")]
+    BundleUnavailable(String),
+
+    #[error("Certification could not be checked: {0}")]
+    InvalidCertification(String),
+
+    #[error("Invalid fields: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidFields(Vec<FieldError>),
+
+    #[error("Proof verification failed: {0}")]
+    VerificationFailed(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("Proof generation failed: {0}")]
+    ProvingFailed(String),
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidRequest(_) => "invalid_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::ProofNotFound(_) => "proof_not_found",
+            ApiError::BatchNotFound(_) => "batch_not_found",
+            ApiError::SchemaNotFound(_) => "schema_not_found",
+            ApiError::CertificateNotInBatch { .. } => "certificate_not_in_batch",
+            ApiError::ProofUnreadable(_) => "proof_unreadable",
+            ApiError::ProofCorrupt(_) => "proof_corrupt",
+            ApiError::WrongProofMode { .. } => "wrong_proof_mode",
+            ApiError::BundleUnavailable(_) => "bundle_unavailable",
+            ApiError::InvalidCertification(_) => "invalid_certification",
+            ApiError::InvalidFields(_) => "invalid_fields",
+            ApiError::VerificationFailed(_) => "verification_failed",
+            ApiError::QuotaExceeded(_) => "quota_exceeded",
+            ApiError::ProvingFailed(_) => "proving_failed",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::ProofNotFound(_)
+            | ApiError::BatchNotFound(_)
+            | ApiError::SchemaNotFound(_)
+            | ApiError::CertificateNotInBatch { .. } => StatusCode::NOT_FOUND,
+            ApiError::WrongProofMode { .. } | ApiError::BundleUnavailable(_) => StatusCode::CONFLICT,
+            ApiError::InvalidCertification(_)
+            | ApiError::InvalidFields(_)
+            | ApiError::VerificationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::QuotaExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::ProofUnreadable(_)
+            | ApiError::ProofCorrupt(_)
+            | ApiError::ProvingFailed(_)
+            | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Classify a failure to load a stored record: unreadable, malformed or anything else.
+    /// Stores may add context on the way up, so the whole chain of causes is looked at.
+    pub fn from_record_load(err: anyhow::Error, id: &str) -> ApiError {
+        if err.chain().any(|cause| cause.is::<std::io::Error>()) {
+            return ApiError::ProofUnreadable(id.to_string());
+        }
+        if err.chain().any(|cause| cause.is::<serde_json::Error>()) {
+            return ApiError::ProofCorrupt(id.to_string());
+        }
+        ApiError::Internal(err.to_string())
+    }
+}
+
+impl warp::reject::Reject for ApiError {}
+
+/// RFC 7807 problem details body shared by every error response
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    pub r#type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: String,
+    /// Each offending field of the request, for `invalid_fields`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &str, detail: String) -> Self {
+        Problem {
+            r#type: format!("/problems/{}", code.replace('_', "-")),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail,
+            code: code.to_string(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn into_reply(self) -> impl Reply {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        warp::reply::with_status(
+            warp::reply::with_header(warp::reply::json(&self), "Content-Type", "application/problem+json"),
+            status,
+        )
+    }
+}
+
+impl From<&ApiError> for Problem {
+    fn from(err: &ApiError) -> Self {
+        let mut problem = Problem::new(err.status(), err.code(), err.to_string());
+        if let ApiError::InvalidFields(errors) = err {
+            problem.errors = errors.clone();
+        }
+        problem
+    }
+}
+
+/// Turn any rejection, ours or warp's own, into a problem details response
+pub async fn handle_rejection(rejection: Rejection) -> Result<impl Reply, Infallible> {
+    let problem = if let Some(err) = rejection.find::<ApiError>() {
+        if err.status().is_server_error() {
+            tracing::error!("Request failed: {}", err);
+        }
+        Problem::from(err)
+    } else if rejection.is_not_found() {
+        Problem::new(StatusCode::NOT_FOUND, "not_found", "No such route".to_string())
+    } else if let Some(err) = rejection.find::<warp::filters::body::BodyDeserializeError>() {
+        Problem::new(StatusCode::BAD_REQUEST, "invalid_body", err.to_string())
+    } else if let Some(err) = rejection.find::<warp::reject::InvalidQuery>() {
+        Problem::new(StatusCode::BAD_REQUEST, "invalid_query", err.to_string())
+    } else if rejection.find::<warp::reject::UnsupportedMediaType>().is_some() {
+        Problem::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type", "Expected a JSON body".to_string())
+    } else if rejection.find::<warp::reject::PayloadTooLarge>().is_some() {
+        Problem::new(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", "Request body is too large".to_string())
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        Problem::new(StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed", "Method not allowed for this route".to_string())
+    } else {
+        tracing::error!("Unhandled rejection: {:?}", rejection);
+        Problem::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", "Unhandled error".to_string())
+    };
+
+    Ok(problem.into_reply())
+}
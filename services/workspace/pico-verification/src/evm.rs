@@ -0,0 +1,337 @@
+use anyhow::{Context, Result};
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_groth16::{prepare_verifying_key, Groth16, Proof, VerifyingKey};
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+use crate::store::ProofStore;
+use crate::types::{EvmProofInputs, ProofId, ProofRecord, ProvingMode};
+
+/// Directory under the output dir holding the shared EVM setup and the proof being wrapped
+pub const EVM_DIR: &str = "evm";
+
+/// File written by the EVM setup phase; its absence means setup must run first
+pub const EVM_SETUP_MARKER: &str = "vm_pk";
+
+/// Proof inputs the EVM prover writes next to the setup
+pub const EVM_INPUTS: &str = "inputs.json";
+
+/// Lock file serialising use of the EVM directory between processes sharing an output dir
+const EVM_LOCK: &str = ".lock";
+
+/// Serialises use of the EVM directory between threads of this process
+static EVM_DIR_LOCK: Mutex<()> = Mutex::new(());
+
+/// Exclusive use of the EVM directory, released on drop
+pub struct SetupLock {
+    _guard: MutexGuard<'static, ()>,
+    _file: File,
+}
+
+/// Wait for exclusive use of `evm_dir`, creating it if needed
+pub fn lock_setup(evm_dir: &Path) -> Result<SetupLock> {
+    let guard = EVM_DIR_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    std::fs::create_dir_all(evm_dir)?;
+    let file = File::create(evm_dir.join(EVM_LOCK))?;
+    file.lock().with_context(|| format!("Failed to lock {:?}", evm_dir))?;
+    Ok(SetupLock { _guard: guard, _file: file })
+}
+
+/// Whether the setup is in `output_dir`, fetching the one another instance shared through `store`
+/// if not; the caller holds the [`lock_setup`] lock
+pub(crate) fn setup_ready(store: &dyn ProofStore, output_dir: &Path) -> bool {
+    let marker = output_dir.join(EVM_DIR).join(EVM_SETUP_MARKER);
+    if marker.exists() {
+        return true;
+    }
+    match store.get_artifacts(output_dir, EVM_DIR) {
+        Ok(true) => {
+            tracing::info!("Fetched the EVM setup shared by another instance");
+            marker.exists()
+        }
+        Ok(false) => false,
+        Err(err) => {
+            tracing::warn!("Failed to fetch the shared EVM setup: {:#}", err);
+            false
+        }
+    }
+}
+
+/// For EVM proofs, make sure the setup they are checked against is in `output_dir`, fetching it
+/// through `store` if another instance made it. Other modes need no setup.
+pub fn ensure_setup(store: &dyn ProofStore, output_dir: &Path, mode: ProvingMode) {
+    if mode != ProvingMode::Evm || output_dir.join(EVM_DIR).join(EVM_SETUP_MARKER).exists() {
+        return;
+    }
+    match lock_setup(&output_dir.join(EVM_DIR)) {
+        Ok(_lock) => {
+            setup_ready(store, output_dir);
+        }
+        Err(err) => tracing::warn!("Failed to lock the EVM setup: {:#}", err),
+    }
+}
+
+/// Entry point of Pico's Solidity verifier that the calldata targets
+pub const VERIFIER_FUNCTION: &str = "verifyPicoProof(bytes32,bytes,uint256[8])";
+
+/// Solidity verifier sources plus calldata for verifying one proof on-chain
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvmExport {
+    pub proof_id: ProofId,
+    pub function: String,
+    pub calldata: String,
+    pub contracts: BTreeMap<String, String>,
+}
+
+/// Groth16 verifying key of the EVM setup, as the `uint256` constants of the Solidity verifier it
+/// generated (`ALPHA_X`, `BETA_NEG_X_0`, ..., `PUB_1_Y`), in decimal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Groth16Key(pub BTreeMap<String, String>);
+
+/// Directory under the EVM dir with the verifying key digest of each guest proven there, by program hash
+const GUEST_VKEYS: &str = "guests";
+
+/// What an EVM proof of one guest is checked against
+pub struct EvmSetup {
+    pub key: Groth16Key,
+    /// Digest of the guest's RISC-V verifying key as the prover reported it, if it was proven here
+    pub guest_vkey: Option<String>,
+}
+
+/// The setup in `output_dir` and what its prover reported for the guest with `program_hash`
+pub fn setup(output_dir: &Path, program_hash: &str) -> Result<EvmSetup> {
+    Ok(EvmSetup { key: setup_key(output_dir)?, guest_vkey: guest_vkey(output_dir, program_hash) })
+}
+
+/// The verifying key digest the prover reported for the guest with `program_hash` when it
+/// proved it in `output_dir`
+pub fn guest_vkey(output_dir: &Path, program_hash: &str) -> Option<String> {
+    if !program_hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let path = output_dir.join(EVM_DIR).join(GUEST_VKEYS).join(program_hash.to_ascii_lowercase());
+    Some(std::fs::read_to_string(path).ok()?.trim().to_string())
+}
+
+/// Keep the verifying key digest the prover reported for a guest, so later proofs of it can be
+/// checked; `true` if it was not known before. The caller holds the [`lock_setup`] lock.
+pub fn record_guest_vkey(evm_dir: &Path, program_hash: &str, riscv_vkey: &str) -> Result<bool> {
+    let dir = evm_dir.join(GUEST_VKEYS);
+    let path = dir.join(program_hash.to_ascii_lowercase());
+    if path.exists() {
+        return Ok(false);
+    }
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(&path, riscv_vkey)?;
+    Ok(true)
+}
+
+/// The verifying key of the setup in `output_dir`, which every EVM proof made there is checked against
+pub fn setup_key(output_dir: &Path) -> Result<Groth16Key> {
+    let mut constants = BTreeMap::new();
+    for source in read_contracts(&output_dir.join(EVM_DIR))?.values() {
+        for line in source.lines() {
+            let Some(declaration) = line.trim().strip_prefix("uint256 constant ") else {
+                continue;
+            };
+            let Some((name, value)) = declaration.trim_end_matches(';').split_once('=') else {
+                continue;
+            };
+            let value = BigUint::from_bytes_be(&parse_word(value.trim())?);
+            constants.insert(name.trim().to_string(), value.to_string());
+        }
+    }
+    Ok(Groth16Key(constants))
+}
+
+/// Check the Groth16 proof of an EVM proof the way the Solidity verifier does, for the guest
+/// whose RISC-V verifying key digest is `riscv_vkey`
+pub fn verify_groth16(key: &Groth16Key, inputs: &EvmProofInputs, riscv_vkey: &str) -> Result<()> {
+    if parse_word(&inputs.riscv_v_key)? != parse_word(riscv_vkey)? {
+        anyhow::bail!("Proof is for the guest with verifying key {}, not {}", inputs.riscv_v_key, riscv_vkey);
+    }
+    if inputs.proof.len() != 8 {
+        anyhow::bail!("Expected 8 proof elements, got {}", inputs.proof.len());
+    }
+
+    // The verifier keeps beta, gamma and delta negated, with G2 coordinates as (real, imaginary)
+    let vk = VerifyingKey::<Bn254> {
+        alpha_g1: key.g1("ALPHA")?,
+        beta_g2: -key.g2("BETA_NEG")?,
+        gamma_g2: -key.g2("GAMMA_NEG")?,
+        delta_g2: -key.g2("DELTA_NEG")?,
+        gamma_abc_g1: vec![key.g1("CONSTANT")?, key.g1("PUB_0")?, key.g1("PUB_1")?],
+    };
+
+    // Proof words are A, then B with the imaginary part of each coordinate first, then C
+    let words = inputs.proof.iter().map(|element| field(element)).collect::<Result<Vec<Fq>>>()?;
+    let proof = Proof::<Bn254> {
+        a: g1_point(words[0], words[1])?,
+        b: g2_point(Fq2::new(words[3], words[2]), Fq2::new(words[5], words[4]))?,
+        c: g1_point(words[6], words[7])?,
+    };
+
+    // Public inputs: the guest's verifying key and the SHA-256 of its public values cut to 253 bits
+    let public_values = hex::decode(inputs.public_values.trim_start_matches("0x"))?;
+    let mut digest: [u8; 32] = Sha256::digest(&public_values).into();
+    digest[0] &= 0x1f;
+    let public_inputs = [parse_word(&inputs.riscv_v_key)?, digest]
+        .iter()
+        .map(|word| Fr::from_str(&BigUint::from_bytes_be(word).to_string())
+            .map_err(|_| anyhow::anyhow!("Public input is not a BN254 scalar")))
+        .collect::<Result<Vec<_>>>()?;
+
+    if !Groth16::<Bn254>::verify_proof(&prepare_verifying_key(&vk), &proof, &public_inputs)? {
+        anyhow::bail!("Groth16 proof does not verify against the EVM setup");
+    }
+    Ok(())
+}
+
+impl Groth16Key {
+    fn constant(&self, name: &str) -> Result<Fq> {
+        let value = self.0.get(name)
+            .ok_or_else(|| anyhow::anyhow!("EVM verifying key has no {}", name))?;
+        field(value)
+    }
+
+    fn g1(&self, name: &str) -> Result<G1Affine> {
+        g1_point(self.constant(&format!("{}_X", name))?, self.constant(&format!("{}_Y", name))?)
+    }
+
+    fn g2(&self, name: &str) -> Result<G2Affine> {
+        let x = Fq2::new(self.constant(&format!("{}_X_0", name))?, self.constant(&format!("{}_X_1", name))?);
+        let y = Fq2::new(self.constant(&format!("{}_Y_0", name))?, self.constant(&format!("{}_Y_1", name))?);
+        g2_point(x, y)
+    }
+}
+
+fn field(value: &str) -> Result<Fq> {
+    Fq::from_str(&BigUint::from_bytes_be(&parse_word(value)?).to_string())
+        .map_err(|_| anyhow::anyhow!("Not a BN254 field element: {}", value))
+}
+
+fn g1_point(x: Fq, y: Fq) -> Result<G1Affine> {
+    let point = G1Affine::new_unchecked(x, y);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        anyhow::bail!("G1 point is not on the BN254 curve");
+    }
+    Ok(point)
+}
+
+fn g2_point(x: Fq2, y: Fq2) -> Result<G2Affine> {
+    let point = G2Affine::new_unchecked(x, y);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        anyhow::bail!("G2 point is not on the BN254 curve");
+    }
+    Ok(point)
+}
+
+/// Solidity sources in `evm_dir`, by file name
+fn read_contracts(evm_dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut contracts = BTreeMap::new();
+    for entry in std::fs::read_dir(evm_dir).with_context(|| format!("Failed to read {:?}", evm_dir))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "sol") {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            contracts.insert(name, std::fs::read_to_string(&path)?);
+        }
+    }
+    if contracts.is_empty() {
+        anyhow::bail!("No Solidity verifier found in {:?}, run an evm proof first", evm_dir);
+    }
+    Ok(contracts)
+}
+
+/// Build the Solidity verifier export for an EVM-wrapped proof
+pub fn export_verifier(output_dir: &Path, record: &ProofRecord) -> Result<EvmExport> {
+    if record.mode != ProvingMode::Evm {
+        anyhow::bail!("Proof {} was generated in {:?} mode, only evm proofs can be verified on-chain", record.id, record.mode);
+    }
+    let inputs = record.evm_inputs.as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Proof {} has no EVM proof inputs", record.id))?;
+
+    // The verifier contracts are generated once during EVM setup and shared by all proofs
+    let contracts = read_contracts(&output_dir.join(EVM_DIR))?;
+
+    Ok(EvmExport {
+        proof_id: record.id.clone(),
+        function: VERIFIER_FUNCTION.to_string(),
+        calldata: format!("0x{}", hex::encode(encode_calldata(inputs)?)),
+        contracts,
+    })
+}
+
+/// Write the export as `<proof_id>-evm/` containing the contracts and `calldata.json`
+pub fn write_export(export: &EvmExport, output_dir: &Path) -> Result<PathBuf> {
+    let export_dir = output_dir.join(format!("{}-evm", export.proof_id));
+    std::fs::create_dir_all(&export_dir)?;
+
+    for (name, source) in &export.contracts {
+        std::fs::write(export_dir.join(name), source)?;
+    }
+    std::fs::write(export_dir.join("calldata.json"), serde_json::to_string_pretty(export)?)?;
+
+    Ok(export_dir)
+}
+
+/// ABI-encode a `verifyPicoProof(bytes32 riscvVkey, bytes publicValues, uint256[8] proof)` call
+pub fn encode_calldata(inputs: &EvmProofInputs) -> Result<Vec<u8>> {
+    if inputs.proof.len() != 8 {
+        anyhow::bail!("Expected 8 proof elements, got {}", inputs.proof.len());
+    }
+
+    let vkey = parse_word(&inputs.riscv_v_key)?;
+    let public_values = hex::decode(inputs.public_values.trim_start_matches("0x"))?;
+
+    let mut calldata = function_selector(VERIFIER_FUNCTION).to_vec();
+
+    // Head: vkey, offset of the dynamic bytes, then the static proof array inline
+    calldata.extend_from_slice(&vkey);
+    calldata.extend_from_slice(&u256_word((32 + 32 + 8 * 32) as u64));
+    for element in &inputs.proof {
+        calldata.extend_from_slice(&parse_word(element)?);
+    }
+
+    // Tail: length-prefixed public values, right-padded to a whole word
+    calldata.extend_from_slice(&u256_word(public_values.len() as u64));
+    calldata.extend_from_slice(&public_values);
+    let padding = (32 - public_values.len() % 32) % 32;
+    calldata.extend(std::iter::repeat_n(0u8, padding));
+
+    Ok(calldata)
+}
+
+/// First four bytes of the keccak256 hash of a function signature
+pub fn function_selector(signature: &str) -> [u8; 4] {
+    let hash = Keccak256::digest(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn u256_word(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// Parse a hex (`0x`-prefixed) or decimal string into a big-endian 32-byte word
+fn parse_word(value: &str) -> Result<[u8; 32]> {
+    let number = match value.strip_prefix("0x") {
+        Some(hex_digits) => BigUint::parse_bytes(hex_digits.as_bytes(), 16),
+        None => BigUint::parse_bytes(value.as_bytes(), 10),
+    }
+    .ok_or_else(|| anyhow::anyhow!("Invalid 256-bit value: {}", value))?;
+
+    let bytes = number.to_bytes_be();
+    if bytes.len() > 32 {
+        anyhow::bail!("Value does not fit in 256 bits: {}", value);
+    }
+    let mut word = [0u8; 32];
+    word[32 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(word)
+}
@@ -0,0 +1,28 @@
+//! Guest programs built into this binary by `build.rs`.
+//!
+//! A proof is only meaningful together with the program that produced it, so every record
+//! carries the program hash and is only accepted for versions in the [registry](crate::registry).
+
+use crate::registry::OutputLayout;
+
+/// The single-certification guest
+pub const ELF: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/material-verification.elf"));
+
+/// Hex SHA-256 of [`ELF`]
+pub const PROGRAM_HASH: &str = env!("GUEST_PROGRAM_HASH");
+
+/// Crate version [`ELF`] was built from
+pub const VERSION: &str = env!("GUEST_VERSION");
+
+pub const OUTPUT_LAYOUT: OutputLayout = OutputLayout::ComplianceV1;
+
+/// The batch guest
+pub const BATCH_ELF: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/material-verification-batch.elf"));
+
+/// Hex SHA-256 of [`BATCH_ELF`]
+pub const BATCH_PROGRAM_HASH: &str = env!("BATCH_PROGRAM_HASH");
+
+/// Crate version [`BATCH_ELF`] was built from
+pub const BATCH_VERSION: &str = env!("BATCH_GUEST_VERSION");
+
+pub const BATCH_OUTPUT_LAYOUT: OutputLayout = OutputLayout::BatchV1;
@@ -0,0 +1,147 @@
+//! What a proof says, read without verifying it.
+//!
+//! Inspection decodes the public values with the decoder of the guest version that produced
+//! them. Proofs of unknown programs are still described, with their raw public values only.
+
+use anyhow::Result;
+use serde::Serialize;
+use crate::batch;
+use crate::bundle::ProofBundle;
+use crate::circuit;
+use crate::registry::{self, GuestKind};
+use crate::types::{BatchProofRecord, ProgramInfo, ProofId, ProofMetrics, ProofRecord, PropertyResult, ProvingMode};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofKind {
+    Proof,
+    Batch,
+    Bundle,
+}
+
+impl ProofKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ProofKind::Proof => "proof",
+            ProofKind::Batch => "batch",
+            ProofKind::Bundle => "bundle",
+        }
+    }
+}
+
+/// Everything known about a proof without checking it
+#[derive(Debug, Clone, Serialize)]
+pub struct Inspection {
+    pub kind: ProofKind,
+    pub id: ProofId,
+    pub mode: ProvingMode,
+    pub proved_at: String,
+    pub certificates: Vec<String>,
+    pub program_hash: Option<String>,
+    /// The guest version, if the program is a known one
+    pub program: Option<ProgramInfo>,
+    /// Why the public values could not be decoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undecoded: Option<String>,
+    /// Hex-encoded values committed by the guest
+    pub public_values: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<DecodedOutputs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<ProofMetrics>,
+    /// Public key the bundle claims to be signed with; the signature is not checked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+}
+
+/// Public values as the guest meant them
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum DecodedOutputs {
+    Certificate {
+        overall_compliance: String,
+        properties: Vec<PropertyResult>,
+    },
+    Batch {
+        merkle_root: String,
+        verdicts: Vec<BatchVerdict>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchVerdict {
+    pub certificate_id: String,
+    pub index: usize,
+    pub overall_compliance: String,
+}
+
+pub fn inspect_proof(record: &ProofRecord) -> Inspection {
+    let version = registry::resolve(&record.id, record.program_hash.as_deref(), GuestKind::Certificate);
+    let decoded = version.as_ref()
+        .map_err(|err| anyhow::anyhow!("{}", err))
+        .and_then(|version| circuit::verification_result(record, &record.certification, version, false))
+        .map(|result| {
+            let mut properties: Vec<_> = result.properties.into_values().collect();
+            properties.sort_by(|a, b| (a.is_private, &a.property).cmp(&(b.is_private, &b.property)));
+            DecodedOutputs::Certificate { overall_compliance: result.overall_compliance, properties }
+        });
+
+    Inspection {
+        kind: ProofKind::Proof,
+        id: record.id.clone(),
+        mode: record.mode,
+        proved_at: record.timestamp.clone(),
+        certificates: vec![record.certification.certificate_id.clone()],
+        program_hash: record.program_hash.clone(),
+        program: version.as_ref().ok().map(ProgramInfo::from),
+        undecoded: decoded.as_ref().err().map(|err| err.to_string()),
+        public_values: hex::encode(record.pv_stream.as_deref().unwrap_or_default()),
+        outputs: decoded.ok(),
+        metrics: record.metrics.clone(),
+        issuer: None,
+    }
+}
+
+pub fn inspect_batch(record: &BatchProofRecord) -> Inspection {
+    let version = registry::resolve(&record.id, record.program_hash.as_deref(), GuestKind::Batch);
+    let decoded = version.as_ref()
+        .map_err(|err| anyhow::anyhow!("{}", err))
+        .and_then(|_| batch::decode_batch_output(record.pv_stream.as_deref()))
+        .map(|output| DecodedOutputs::Batch {
+            merkle_root: hex::encode(output.merkle_root),
+            verdicts: record.entries.iter()
+                .map(|entry| BatchVerdict {
+                    certificate_id: entry.certificate_id.clone(),
+                    index: entry.index,
+                    overall_compliance: match output.verdicts.get(entry.index) {
+                        Some(true) => "PASS".to_string(),
+                        Some(false) => "FAIL".to_string(),
+                        None => "MISSING".to_string(),
+                    },
+                })
+                .collect(),
+        });
+
+    Inspection {
+        kind: ProofKind::Batch,
+        id: record.id.clone(),
+        mode: record.mode,
+        proved_at: record.timestamp.clone(),
+        certificates: record.entries.iter().map(|entry| entry.certificate_id.clone()).collect(),
+        program_hash: record.program_hash.clone(),
+        program: version.as_ref().ok().map(ProgramInfo::from),
+        undecoded: decoded.as_ref().err().map(|err| err.to_string()),
+        public_values: hex::encode(record.pv_stream.as_deref().unwrap_or_default()),
+        outputs: decoded.ok(),
+        metrics: None,
+        issuer: None,
+    }
+}
+
+pub fn inspect_bundle(bundle: &ProofBundle) -> Result<Inspection> {
+    Ok(Inspection {
+        kind: ProofKind::Bundle,
+        issuer: Some(bundle.signature.public_key.clone()),
+        ..inspect_proof(&bundle.payload.record()?)
+    })
+}
@@ -0,0 +1,272 @@
+use anyhow::{Context, Result};
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::AffineRepr;
+use ark_groth16::{prepare_verifying_key, Groth16, Proof, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::types::{ComplianceSummary, PropertyResult, VerificationResult, ZkpInfo};
+
+/// Scale factor the legacy generator applied to turn decimals into field elements
+const LEGACY_SCALE: f64 = 1_000_000.0;
+
+/// Groth16 proof as written to `proof.json` by snarkjs
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnarkjsProof {
+    pub pi_a: Vec<String>,
+    pub pi_b: Vec<Vec<String>>,
+    pub pi_c: Vec<String>,
+    pub protocol: String,
+    pub curve: String,
+}
+
+/// Groth16 verification key as exported by `snarkjs zkey export verificationkey`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnarkjsVerificationKey {
+    pub protocol: String,
+    pub curve: String,
+    #[serde(rename = "nPublic")]
+    pub n_public: usize,
+    pub vk_alpha_1: Vec<String>,
+    pub vk_beta_2: Vec<Vec<String>>,
+    pub vk_gamma_2: Vec<Vec<String>>,
+    pub vk_delta_2: Vec<Vec<String>>,
+    #[serde(rename = "IC")]
+    pub ic: Vec<Vec<String>>,
+}
+
+/// The legacy `verification.json` report, used only to label properties
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyReport {
+    pub material_id: String,
+    pub material_type: String,
+    pub standard: String,
+    pub properties: HashMap<String, LegacyProperty>,
+    pub zkp_info: Option<LegacyZkpInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyProperty {
+    pub value: serde_json::Value,
+    pub is_private: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyZkpInfo {
+    pub circuit: String,
+}
+
+/// Everything needed to check one legacy certificate
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LegacyProofBundle {
+    pub proof: SnarkjsProof,
+    pub public_signals: Vec<String>,
+    pub verification_key: SnarkjsVerificationKey,
+    pub report: Option<LegacyReport>,
+}
+
+/// Read a legacy proof bundle from the individual snarkjs output files
+pub fn read_legacy_bundle(
+    proof_path: &Path,
+    public_path: &Path,
+    vkey_path: &Path,
+    report_path: Option<&Path>,
+) -> Result<LegacyProofBundle> {
+    let report = match report_path {
+        Some(path) => Some(read_json(path)?),
+        None => None,
+    };
+
+    Ok(LegacyProofBundle {
+        proof: read_json(proof_path)?,
+        public_signals: read_json(public_path)?,
+        verification_key: read_json(vkey_path)?,
+        report,
+    })
+}
+
+/// Verify a legacy snarkjs Groth16 proof and generate verification results
+pub fn verify_legacy_proof(bundle: &LegacyProofBundle) -> Result<VerificationResult> {
+    let verified = verify_groth16(&bundle.proof, &bundle.public_signals, &bundle.verification_key)?;
+
+    // Public signals are laid out as [property_compliance[n], all_compliant, min_thresholds[n], max_thresholds[n]]
+    let signals = &bundle.public_signals;
+    if signals.len() % 3 != 1 {
+        anyhow::bail!("Unexpected number of public signals for legacy circuit: {}", signals.len());
+    }
+    let count = (signals.len() - 1) / 3;
+    let all_compliant = signals[count] == "1";
+
+    // The legacy generator fed properties to the circuit in sorted name order
+    let mut names: Vec<String> = match &bundle.report {
+        Some(report) if report.properties.len() == count => report.properties.keys().cloned().collect(),
+        _ => (0..count).map(|i| format!("property_{}", i)).collect(),
+    };
+    names.sort();
+
+    let mut properties = HashMap::new();
+    let mut private_count = 0;
+    for (i, name) in names.iter().enumerate() {
+        let reported = bundle.report.as_ref().and_then(|r| r.properties.get(name));
+        let is_private = reported.map(|p| p.is_private).unwrap_or(false);
+        if is_private {
+            private_count += 1;
+        }
+
+        let (value, thresholds) = if is_private {
+            (json!("**REDACTED**"), json!("**REDACTED**"))
+        } else {
+            (
+                reported.map(|p| p.value.clone()).unwrap_or(serde_json::Value::Null),
+                json!({
+                    "min": unscale(&signals[count + 1 + i])?,
+                    "max": unscale(&signals[2 * count + 1 + i])?
+                }),
+            )
+        };
+
+        properties.insert(name.clone(), PropertyResult {
+            property: name.clone(),
+            value,
+            thresholds,
+            compliant: verified && signals[i] == "1",
+            is_private,
+        });
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)?
+        .as_millis()
+        .to_string();
+
+    let passing = properties.values().filter(|p| p.compliant).count();
+    let total = properties.len();
+
+    let (material_id, material_type, standard) = match &bundle.report {
+        Some(report) => (report.material_id.clone(), report.material_type.clone(), report.standard.clone()),
+        None => ("unknown".to_string(), "unknown".to_string(), "unknown".to_string()),
+    };
+    let circuit = bundle.report.as_ref()
+        .and_then(|r| r.zkp_info.as_ref())
+        .map(|z| z.circuit.clone())
+        .unwrap_or_else(|| "material_verifier_legacy".to_string());
+
+    Ok(VerificationResult {
+        material_id,
+        material_type,
+        standard,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        verification_id: format!("VER-LEGACY-{}-{}", now, rand::random::<u16>()),
+        overall_compliance: if verified && all_compliant { "PASS".to_string() } else { "FAIL".to_string() },
+        properties,
+        compliance_summary: ComplianceSummary {
+            total_properties: total,
+            public_properties: total - private_count,
+            private_properties: private_count,
+            passing_properties: passing,
+            failing_properties: total - passing,
+        },
+        zkp_info: ZkpInfo {
+            implemented: true,
+            proof_type: "Groth16 ZK Proof".to_string(),
+            circuit,
+            proof: serde_json::to_string(&bundle.proof)?,
+            public_signals: serde_json::to_string(signals)?,
+            verified,
+            program: None,
+        },
+    })
+}
+
+/// Check the Groth16 pairing equation for a snarkjs proof on BN254
+fn verify_groth16(proof: &SnarkjsProof, public_signals: &[String], vkey: &SnarkjsVerificationKey) -> Result<bool> {
+    if proof.protocol != "groth16" || vkey.protocol != "groth16" {
+        anyhow::bail!("Only groth16 proofs are supported, got {}", proof.protocol);
+    }
+    if proof.curve != "bn128" || vkey.curve != "bn128" {
+        anyhow::bail!("Only bn128 proofs are supported, got {}", proof.curve);
+    }
+    if public_signals.len() != vkey.n_public || vkey.ic.len() != vkey.n_public + 1 {
+        anyhow::bail!(
+            "Verification key expects {} public signals, got {}",
+            vkey.n_public,
+            public_signals.len()
+        );
+    }
+
+    let vk = VerifyingKey::<Bn254> {
+        alpha_g1: parse_g1(&vkey.vk_alpha_1)?,
+        beta_g2: parse_g2(&vkey.vk_beta_2)?,
+        gamma_g2: parse_g2(&vkey.vk_gamma_2)?,
+        delta_g2: parse_g2(&vkey.vk_delta_2)?,
+        gamma_abc_g1: vkey.ic.iter().map(|p| parse_g1(p)).collect::<Result<_>>()?,
+    };
+
+    let proof = Proof::<Bn254> {
+        a: parse_g1(&proof.pi_a)?,
+        b: parse_g2(&proof.pi_b)?,
+        c: parse_g1(&proof.pi_c)?,
+    };
+
+    let inputs = public_signals.iter()
+        .map(|s| Fr::from_str(s).map_err(|_| anyhow::anyhow!("Invalid public signal: {}", s)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let pvk = prepare_verifying_key(&vk);
+    Ok(Groth16::<Bn254>::verify_proof(&pvk, &proof, &inputs)?)
+}
+
+fn parse_fq(s: &str) -> Result<Fq> {
+    Fq::from_str(s).map_err(|_| anyhow::anyhow!("Invalid field element: {}", s))
+}
+
+/// Parse a projective G1 point in snarkjs `[x, y, z]` form
+fn parse_g1(coords: &[String]) -> Result<G1Affine> {
+    if coords.len() != 3 {
+        anyhow::bail!("Expected 3 coordinates for G1 point, got {}", coords.len());
+    }
+    if coords[2] == "0" {
+        return Ok(G1Affine::zero());
+    }
+
+    let point = G1Affine::new_unchecked(parse_fq(&coords[0])?, parse_fq(&coords[1])?);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        anyhow::bail!("G1 point is not on the BN254 curve");
+    }
+    Ok(point)
+}
+
+/// Parse a projective G2 point in snarkjs `[[x0, x1], [y0, y1], [z0, z1]]` form
+fn parse_g2(coords: &[Vec<String>]) -> Result<G2Affine> {
+    if coords.len() != 3 || coords.iter().any(|c| c.len() != 2) {
+        anyhow::bail!("Expected 3 coordinate pairs for G2 point");
+    }
+    if coords[2][0] == "0" && coords[2][1] == "0" {
+        return Ok(G2Affine::zero());
+    }
+
+    let x = Fq2::new(parse_fq(&coords[0][0])?, parse_fq(&coords[0][1])?);
+    let y = Fq2::new(parse_fq(&coords[1][0])?, parse_fq(&coords[1][1])?);
+    let point = G2Affine::new_unchecked(x, y);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        anyhow::bail!("G2 point is not on the BN254 curve");
+    }
+    Ok(point)
+}
+
+fn unscale(signal: &str) -> Result<f64> {
+    let raw: f64 = signal.parse().with_context(|| format!("Invalid threshold signal: {}", signal))?;
+    Ok(raw / LEGACY_SCALE)
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    Ok(serde_json::from_str(&content)?)
+}
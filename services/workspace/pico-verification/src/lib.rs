@@ -0,0 +1,22 @@
+pub mod api;
+pub mod audit;
+pub mod auth;
+pub mod batch;
+pub mod bundle;
+pub mod circuit;
+pub mod config;
+pub mod error;
+pub mod evm;
+pub mod guest;
+pub mod inspect;
+pub mod legacy;
+pub mod metrics;
+pub mod registry;
+pub mod report;
+pub mod schema;
+pub mod shipment;
+pub mod store;
+pub mod tenant;
+pub mod tls;
+pub mod types;
+pub mod validation;
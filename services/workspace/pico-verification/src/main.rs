@@ -1,78 +1,817 @@
 use anyhow::Result;
-use clap::Parser;
-use std::path::PathBuf;
-use tracing::{info, Level};
-use tracing_subscriber::FmtSubscriber;
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
 
-mod circuit;
-mod types;
-mod api; // New module
+use material_pico_verify::bundle::ProofBundle;
+use material_pico_verify::config::{Config, LogConfig, LogFormat, TlsConfig};
+use material_pico_verify::report::{self, OutputFormat, Table, Tabular};
+use material_pico_verify::store::ProofStore;
+use material_pico_verify::types::{BatchProofRecord, ProofId, ProofRecord};
+use material_pico_verify::{
+    api, audit, auth, batch, bundle, circuit, evm, guest, inspect, legacy, registry, shipment, store, tenant, types, validation,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Path to input certification file
-    #[arg(short, long)]
-    input: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+
+    /// TOML configuration file; flags and PICO_* variables override its settings
+    #[arg(short, long, env = "PICO_CONFIG", global = true)]
+    config: Option<PathBuf>,
 
     /// Path to output directory
-    #[arg(short, long)]
-    output: PathBuf,
-    
-    /// API port
-    #[arg(short, long, default_value = "3000")]
-    port: u16,
-    
-    /// Run in API mode
-    #[arg(short, long)]
-    api: bool,
-}
-
-#[tokio::main]
-async fn main() -> Result<()> {
+    #[arg(short, long, env = "PICO_OUTPUT_DIR", global = true)]
+    output: Option<PathBuf>,
+
+    /// How results are printed on stdout; logs always go to stderr
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, global = true)]
+    format: OutputFormat,
+
+    /// Where proof records are stored [default: fs]
+    #[arg(long, value_enum, env = "PICO_STORE", global = true)]
+    store: Option<store::StoreBackend>,
+
+    /// Store location: a directory for fs, a database file for sqlite (defaults to the output directory)
+    #[arg(long, env = "PICO_STORE_PATH", global = true)]
+    store_path: Option<PathBuf>,
+
+    /// S3-compatible endpoint for proof payloads; credentials come from the configuration or the AWS environment variables
+    #[arg(long, env = "PICO_S3_ENDPOINT", global = true)]
+    s3_endpoint: Option<String>,
+
+    /// Bucket used with --s3-endpoint
+    #[arg(long, env = "PICO_S3_BUCKET", global = true)]
+    s3_bucket: Option<String>,
+
+    /// Key prefix inside the bucket
+    #[arg(long, env = "PICO_S3_PREFIX", global = true)]
+    s3_prefix: Option<String>,
+
+    /// Region to sign S3 requests for [default: us-east-1]
+    #[arg(long, env = "PICO_S3_REGION", global = true)]
+    s3_region: Option<String>,
+
+    /// Guest ELF to prove with instead of the built-in one (for guest development)
+    #[arg(long, env = "PICO_GUEST_ELF", global = true)]
+    elf: Option<PathBuf>,
+
+    /// Batch guest ELF to prove batches with instead of the built-in one
+    #[arg(long, env = "PICO_BATCH_ELF", global = true)]
+    batch_elf: Option<PathBuf>,
+
+    /// Runtime worker threads (defaults to one per CPU)
+    #[arg(long, env = "PICO_WORKER_THREADS", global = true)]
+    worker_threads: Option<usize>,
+
+    /// Log filter such as `info` or `material_pico_verify=debug` [default: info]
+    #[arg(long, env = "PICO_LOG_LEVEL", global = true)]
+    log_level: Option<String>,
+
+    /// Log line format [default: text]
+    #[arg(long, value_enum, env = "PICO_LOG_FORMAT", global = true)]
+    log_format: Option<LogFormat>,
+
+    /// Organisation whose proofs commands work with [default: default]; the API serves all of them
+    #[arg(long, global = true)]
+    org: Option<types::OrgId>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Prove a certification, or several together in one batch proof
+    Prove(ProveArgs),
+    /// Verify a stored proof, a proof bundle or a legacy snarkjs proof; only final proofs count as verified
+    Verify(VerifyArgs),
+    /// Show what a proof contains and decode its public outputs, without verifying it
+    Inspect {
+        /// Id of a stored proof or batch, or a proof record or bundle file
+        proof: String,
+    },
+    /// Check a certification file without proving it
+    Validate {
+        /// Certification JSON file
+        cert: PathBuf,
+    },
+    /// Emulate the guest on a certification to check it and estimate the proving cost, without proving
+    Preflight {
+        /// Certification JSON file
+        cert: PathBuf,
+
+        /// Proving mode to estimate the cost of
+        #[arg(short, long, value_enum, default_value_t = types::ProvingMode::Fast)]
+        mode: types::ProvingMode,
+    },
+    /// Serve the HTTP API
+    Serve(ServerArgs),
+    /// Export the Solidity verifier and calldata for a stored evm proof
+    ExportEvm {
+        proof_id: ProofId,
+    },
+    /// Work with the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print the effective configuration and check it is valid for serving the API
+    Check(ServerArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct ProveArgs {
+    /// Certification JSON files; more than one needs --batch
+    #[arg(value_name = "CERT", required_unless_present_any = ["dir", "manifest"], conflicts_with_all = ["dir", "manifest"])]
+    certs: Vec<PathBuf>,
+
+    /// Prove every *.json certification in this folder, one proof each
+    #[arg(long, conflicts_with_all = ["manifest", "batch", "preflight"])]
+    dir: Option<PathBuf>,
+
+    /// Prove every certification listed in the `file` column of this CSV, one proof each
+    #[arg(long, value_name = "CSV", conflicts_with_all = ["batch", "preflight"])]
+    manifest: Option<PathBuf>,
+
+    /// Certifications of a --dir or --manifest shipment proven at the same time [default: 1]
+    #[arg(short, long, env = "PICO_MAX_CONCURRENT_PROOFS")]
+    jobs: Option<usize>,
+
+    /// Where to keep the shipment summary, which is updated as certificates finish
+    /// (defaults to <output>/shipments/<name>.json)
+    #[arg(long, value_name = "FILE")]
+    summary: Option<PathBuf>,
+
+    /// How much proving work to do
+    #[arg(short, long, value_enum, default_value_t = types::ProvingMode::Fast)]
+    mode: types::ProvingMode,
+
+    /// Prove again even if an identical proof already exists
+    #[arg(long)]
+    force: bool,
+
+    /// Only emulate the guest to check the input and estimate proving cost
+    #[arg(long, conflicts_with = "batch")]
+    preflight: bool,
+
+    /// Prove all certifications together in one batch proof
+    #[arg(long)]
+    batch: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct VerifyArgs {
+    /// Id of a stored proof or batch, a proof record or bundle file, or a snarkjs proof.json with --legacy-public
+    proof: String,
+
+    /// Certification the proof must cover
+    #[arg(long, value_name = "FILE")]
+    cert: Option<PathBuf>,
+
+    /// Certificate to check in a batch proof (defaults to the one in --cert)
+    #[arg(long)]
+    certificate: Option<String>,
+
+    /// Issuer public key (hex) to accept bundles from, on top of bundles.trusted_issuers and
+    /// this server's own signing key; repeat for several
+    #[arg(long)]
+    issuer_key: Vec<String>,
+
+    /// Guest program hash (hex) a bundle's proof must come from
+    #[arg(long)]
+    program_hash: Option<String>,
+
+    /// Path to the legacy public.json signals
+    #[arg(long, requires = "legacy_vkey")]
+    legacy_public: Option<PathBuf>,
+
+    /// Path to the legacy circuit verification key
+    #[arg(long, requires = "legacy_public")]
+    legacy_vkey: Option<PathBuf>,
+
+    /// Optional legacy verification.json used to label properties
+    #[arg(long, requires = "legacy_public")]
+    legacy_report: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ServerArgs {
+    /// API port [default: 3000]
+    #[arg(short, long, env = "PICO_PORT")]
+    port: Option<u16>,
+
+    /// Address the API listens on [default: 0.0.0.0]
+    #[arg(long, env = "PICO_BIND")]
+    bind: Option<std::net::IpAddr>,
+
+    /// Serve the API on a Unix domain socket instead of a TCP port
+    #[arg(long, env = "PICO_UNIX_SOCKET")]
+    unix_socket: Option<PathBuf>,
+
+    /// PEM certificate chain to serve HTTPS with, reloaded when the file changes
+    #[arg(long, env = "PICO_TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, env = "PICO_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Origins browsers may call the API from, comma-separated (any origin when unset)
+    #[arg(long = "cors-origin", env = "PICO_CORS_ORIGINS", value_delimiter = ',', value_name = "ORIGIN")]
+    cors_origins: Vec<String>,
+
+    /// JSON file of API keys and their roles (defaults to <output>/api-keys.json if present)
+    #[arg(long, env = "PICO_API_KEYS")]
+    api_keys: Option<PathBuf>,
+
+    /// Shared secret for HS256 bearer tokens
+    #[arg(long, env = "PICO_JWT_SECRET", hide_env_values = true)]
+    jwt_secret: Option<String>,
+
+    /// Serve the API without authentication, treating every caller as an admin (local development only)
+    #[arg(long, conflicts_with_all = ["api_keys", "jwt_secret"])]
+    insecure_no_auth: bool,
+
+    /// Append-only log of API access decisions (defaults to <output>/audit.jsonl)
+    #[arg(long, env = "PICO_AUDIT_LOG")]
+    audit_log: Option<PathBuf>,
+
+    /// Key the API signs proof bundles with, created on first use (defaults to <output>/issuer.key)
+    #[arg(long, env = "PICO_SIGNING_KEY")]
+    signing_key: Option<PathBuf>,
+
+    /// Also serve /metrics without authentication on this address, e.g. 127.0.0.1:9100
+    #[arg(long, env = "PICO_METRICS_BIND")]
+    metrics_bind: Option<std::net::SocketAddr>,
+
+    /// Proofs the API generates at the same time; further requests wait in the queue
+    #[arg(long, env = "PICO_MAX_CONCURRENT_PROOFS")]
+    max_concurrent_proofs: Option<usize>,
+}
+
+impl Args {
+    /// Flags and PICO_* variables take precedence over the configuration file
+    fn apply(&self, config: &mut Config) {
+        if let Some(output) = &self.output {
+            config.output_dir = Some(output.clone());
+        }
+        if let Some(path) = &self.elf {
+            config.guest.elf = Some(path.clone());
+        }
+        if let Some(path) = &self.batch_elf {
+            config.guest.batch_elf = Some(path.clone());
+        }
+
+        if let Some(backend) = self.store {
+            config.store.backend = backend;
+        }
+        if let Some(path) = &self.store_path {
+            config.store.path = Some(path.clone());
+        }
+        if self.s3_endpoint.is_some() || self.s3_bucket.is_some() || config.store.s3.is_some() {
+            let s3 = config.store.s3.get_or_insert_with(Default::default);
+            let overrides = [
+                (&mut s3.endpoint, &self.s3_endpoint),
+                (&mut s3.bucket, &self.s3_bucket),
+                (&mut s3.prefix, &self.s3_prefix),
+                (&mut s3.region, &self.s3_region),
+            ];
+            for (setting, value) in overrides {
+                if let Some(value) = value {
+                    *setting = value.clone();
+                }
+            }
+        }
+
+        if let Some(threads) = self.worker_threads {
+            config.workers.threads = Some(threads);
+        }
+        if let Some(level) = &self.log_level {
+            config.log.level = level.clone();
+        }
+        if let Some(format) = self.log_format {
+            config.log.format = format;
+        }
+
+        match &self.command {
+            Command::Serve(server) | Command::Config { action: ConfigAction::Check(server) } => server.apply(config),
+            Command::Prove(ProveArgs { jobs: Some(jobs), .. }) => config.workers.max_concurrent_proofs = Some(*jobs),
+            _ => {}
+        }
+    }
+}
+
+impl ServerArgs {
+    fn apply(&self, config: &mut Config) {
+        if let Some(port) = self.port {
+            config.server.port = port;
+        }
+        if let Some(address) = self.bind {
+            config.server.address = address;
+        }
+        if let Some(path) = &self.unix_socket {
+            config.server.unix_socket = Some(path.clone());
+        }
+        if let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) {
+            config.server.tls = Some(TlsConfig { cert: cert.clone(), key: key.clone() });
+        }
+        if !self.cors_origins.is_empty() {
+            config.server.cors.origins = self.cors_origins.clone();
+        }
+        if let Some(path) = &self.signing_key {
+            config.server.signing_key = Some(path.clone());
+        }
+        if let Some(address) = self.metrics_bind {
+            config.server.metrics_address = Some(address);
+        }
+
+        if let Some(path) = &self.api_keys {
+            config.auth.api_keys = Some(path.clone());
+        }
+        if let Some(secret) = &self.jwt_secret {
+            config.auth.jwt_secret = Some(secret.clone());
+        }
+        if self.insecure_no_auth {
+            config.auth.insecure_no_auth = true;
+        }
+        if let Some(path) = &self.audit_log {
+            config.auth.audit_log = Some(path.clone());
+        }
+        if let Some(max) = self.max_concurrent_proofs {
+            config.workers.max_concurrent_proofs = Some(max);
+        }
+    }
+}
+
+/// How a command ended, reported as the exit code so scripts can tell the cases apart.
+///
+/// Errors such as unreadable files or an unreachable store exit with 1, and clap exits
+/// with 2 on invalid usage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Success = 0,
+    /// The proof did not verify as a final proof, or does not cover the given certification
+    Rejected = 3,
+    /// The proof is sound but the material does not comply
+    NonCompliant = 4,
+    /// The certification failed validation
+    Invalid = 5,
+    /// Some certifications of a shipment could not be proven; the summary says why
+    Incomplete = 6,
+}
+
+impl Outcome {
+    fn from_compliance(overall_compliance: &str) -> Self {
+        if overall_compliance == "PASS" { Outcome::Success } else { Outcome::NonCompliant }
+    }
+
+    /// A checked proof counts only if it verified as a final proof; fast proofs never do
+    fn from_verification(verified: bool, overall_compliance: &str) -> Self {
+        if verified { Outcome::from_compliance(overall_compliance) } else { Outcome::Rejected }
+    }
+}
+
+/// Printed instead of a result when a proof is rejected
+#[derive(Debug, Serialize)]
+struct Rejection {
+    proof: String,
+    verified: bool,
+    reason: String,
+}
+
+impl Rejection {
+    fn new(proof: impl ToString, reason: impl ToString) -> Self {
+        Rejection { proof: proof.to_string(), verified: false, reason: reason.to_string() }
+    }
+}
+
+impl Tabular for Rejection {
+    fn table(&self) -> Table {
+        Table::new()
+            .field("Proof", &self.proof)
+            .field("Verified", "no")
+            .field("Reason", &self.reason)
+    }
+}
+
+/// What `prove` produced
+#[derive(Debug, Serialize)]
+struct Proved {
+    proof_id: ProofId,
+    batch: bool,
+    reused: bool,
+    mode: types::ProvingMode,
+    program_hash: String,
+    certificates: Vec<ProvedCertificate>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProvedCertificate {
+    certificate_id: String,
+    overall_compliance: String,
+}
+
+impl Tabular for Proved {
+    fn table(&self) -> Table {
+        let mut table = Table::new()
+            .field(if self.batch { "Batch" } else { "Proof" }, &self.proof_id)
+            .field("Mode", self.mode.label())
+            .field("Reused", if self.reused { "yes" } else { "no" })
+            .field("Program", &self.program_hash)
+            .columns(&["CERTIFICATE", "COMPLIANCE"]);
+        for cert in &self.certificates {
+            table = table.row(vec![cert.certificate_id.clone(), cert.overall_compliance.clone()]);
+        }
+        table
+    }
+}
+
+/// A proof named on the command line
+enum ProofSource {
+    Proof(Box<ProofRecord>),
+    Batch(Box<BatchProofRecord>),
+    Bundle(Box<ProofBundle>),
+}
+
+impl ProofSource {
+    /// Read `proof` as a record or bundle file if one exists at that path, otherwise look it up in the store
+    fn load(proof: &str, store: impl FnOnce() -> Result<Arc<dyn ProofStore>>) -> Result<Self> {
+        let path = Path::new(proof);
+        if path.is_file() {
+            let bytes = std::fs::read(path)?;
+            let value: serde_json::Value = serde_json::from_slice(&bytes)?;
+            return Ok(if value.get("signature").is_some() {
+                ProofSource::Bundle(Box::new(bundle::parse_bundle(&bytes)?))
+            } else if value.get("entries").is_some() {
+                ProofSource::Batch(Box::new(serde_json::from_value(value)?))
+            } else {
+                ProofSource::Proof(Box::new(serde_json::from_value(value)?))
+            });
+        }
+
+        let id: ProofId = proof.parse()
+            .map_err(|_| anyhow::anyhow!("{:?} is neither a file nor a proof id", proof))?;
+        let store = store()?;
+        if let Some(record) = store.get_proof(&id)? {
+            return Ok(ProofSource::Proof(Box::new(record)));
+        }
+        match store.get_batch(&id)? {
+            Some(record) => Ok(ProofSource::Batch(Box::new(record))),
+            None => anyhow::bail!("Proof {} not found", id),
+        }
+    }
+}
+
+/// Issuer keys bundles are accepted from: those given on the command line, the configured ones,
+/// and this server's own signing key if it has one
+fn trusted_issuers(config: &Config, output: &Path, extra: &[String]) -> Result<Vec<String>> {
+    let mut trusted: Vec<String> = extra.iter().chain(&config.bundles.trusted_issuers).cloned().collect();
+    let signing_key = config.server.signing_key.clone().unwrap_or_else(|| output.join("issuer.key"));
+    if signing_key.exists() {
+        trusted.push(bundle::Issuer::load_or_create(&signing_key)?.public_key());
+    }
+    if trusted.is_empty() {
+        anyhow::bail!("No trusted issuers; pass --issuer-key or set bundles.trusted_issuers");
+    }
+    Ok(trusted)
+}
+
+fn init_logging(log: &LogConfig) -> Result<()> {
+    // Results go to stdout, so logs stay out of the way of scripts reading them
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_new(&log.level)?)
+        .with_writer(std::io::stderr);
+    match log.format {
+        LogFormat::Text => tracing::subscriber::set_global_default(builder.finish())?,
+        LogFormat::Json => tracing::subscriber::set_global_default(builder.json().finish())?,
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match start(Args::parse()) {
+        Ok(outcome) => ExitCode::from(outcome as u8),
+        Err(err) => {
+            eprintln!("Error: {:#}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn start(args: Args) -> Result<Outcome> {
+    // Layer the command line over the configuration file
+    let mut config = match &args.config {
+        Some(path) => Config::from_file(path)?,
+        None => Config::default(),
+    };
+    args.apply(&mut config);
+
+    match &args.command {
+        Command::Config { action: ConfigAction::Check(_) } => {
+            print!("{}", config.to_redacted_toml()?);
+            config.validate_server()?;
+            eprintln!("Configuration is valid");
+            return Ok(Outcome::Success);
+        }
+        Command::Validate { cert } => {
+            // Needs nothing but the file, so it works without an output directory
+            let report = validation::validate_file(cert)?;
+            report::print(&report, args.format)?;
+            return Ok(if report.valid { Outcome::Success } else { Outcome::Invalid });
+        }
+        Command::Serve(_) => {
+            if args.org.is_some() {
+                anyhow::bail!("--org selects the organisation of CLI commands; the API serves every organisation");
+            }
+            config.validate_server()?;
+        }
+        _ => config.validate()?,
+    }
+
     // Initialize logging
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)?;
-    
-    info!("Starting Material Verification with Pico...");
-    
-    // Parse command line arguments
-    let args = Args::parse();
-    
+    init_logging(&config.log)?;
+
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    runtime.enable_all();
+    if let Some(threads) = config.workers.threads {
+        runtime.worker_threads(threads);
+    }
+    runtime.build()?.block_on(run(args, config))
+}
+
+async fn run(args: Args, config: Config) -> Result<Outcome> {
     // Ensure output directory exists
-    if !args.output.exists() {
-        std::fs::create_dir_all(&args.output)?;
-    }
-    
-    if args.api {
-        // Start in API mode
-        info!("Starting in API mode on port {}", args.port);
-        api::start_api(args.port, args.output).await?;
-    } else if let Some(input_path) = args.input {
-        // Run in CLI mode (original functionality)
-        info!("Running in CLI mode with input: {:?}", input_path);
-        
-        // Read input certification data
-        let certification = types::read_certification(&input_path)?;
-        info!("Read certification data for material: {}", certification.certificate_id);
-        
-        // Initialize the material verification circuit
-        let circuit = circuit::create_verification_circuit(&certification)?;
-        info!("Created verification circuit");
-        
-        // Generate proof
-        let proof_path = circuit::generate_proof(&circuit, &args.output, &certification)?;
-        info!("Generated proof: {:?}", proof_path);
-        
-        // Write verification results
-        let verification_results = circuit::verify_proof(&proof_path, &args.output, &certification)?;
-        types::write_verification_results(&verification_results, &args.output.join("verification.json"))?;
-        info!("Verification completed successfully");
-    } else {
-        anyhow::bail!("In CLI mode, an input file is required. Use --api to start in API mode.");
-    }
-    
-    Ok(())
-}
\ No newline at end of file
+    let output = config.output_dir().to_path_buf();
+    if !output.exists() {
+        std::fs::create_dir_all(&output)?;
+    }
+
+    match &config.guest.elf {
+        Some(path) => {
+            let hash = circuit::use_guest_elf(path)?;
+            warn!("Running guest {:?} instead of the built-in one; proofs are tagged with program {}", path, hash);
+        }
+        None => info!("Guest program {} (version {})", guest::PROGRAM_HASH, guest::VERSION),
+    }
+    if let Some(path) = &config.guest.batch_elf {
+        let hash = batch::use_batch_elf(path)?;
+        warn!("Running batch guest {:?} instead of the built-in one; batch proofs are tagged with program {}", path, hash);
+    }
+    registry::use_guest_versions(config.guest.versions.clone());
+
+    // Proving artifacts and records of an organisation live apart from those of the others
+    let org = args.org.clone().unwrap_or_default();
+    let work_dir = org.dir(&output);
+    let open_store = || config.store.for_org(&org).open(&work_dir);
+
+    match args.command {
+        Command::Serve(_) => {
+            info!("Starting Material Verification API with Pico...");
+            let signing_key = config.server.signing_key.clone().unwrap_or_else(|| output.join("issuer.key"));
+            let issuer = bundle::Issuer::load_or_create(&signing_key)?;
+            info!("Signing proof bundles as {}", issuer.public_key());
+
+            let auth = if config.auth.insecure_no_auth {
+                tracing::warn!("Authentication is disabled; every caller is treated as an admin");
+                auth::Authenticator::disabled()
+            } else {
+                let api_keys = config.auth.api_keys.clone()
+                    .or_else(|| Some(output.join("api-keys.json")).filter(|path| path.exists()));
+                auth::Authenticator::load(api_keys.as_deref(), config.auth.jwt_secret.clone())?
+            };
+            let audit_log = config.auth.audit_log.clone().unwrap_or_else(|| output.join("audit.jsonl"));
+            let audit = audit::AuditLog::open(&audit_log)?;
+            info!("Recording API access in {:?}", audit_log);
+
+            let max_concurrent_proofs = config.workers.max_concurrent_proofs.unwrap_or(tokio::sync::Semaphore::MAX_PERMITS);
+            let state = api::ApiState {
+                tenants: tenant::Tenants::new(output.clone(), config.store.clone(), config.quotas.clone()),
+                auth,
+                audit,
+                issuer,
+                trusted_issuers: config.bundles.trusted_issuers.clone(),
+                proof_slots: Arc::new(tokio::sync::Semaphore::new(max_concurrent_proofs)),
+            };
+            api::start_api(&config.server, state).await?;
+            Ok(Outcome::Success)
+        }
+        Command::Prove(prove) => {
+            let shipment = match (&prove.dir, &prove.manifest) {
+                (Some(dir), _) => Some(shipment::ShipmentSource::Dir(dir.clone())),
+                (_, Some(manifest)) => Some(shipment::ShipmentSource::Manifest(manifest.clone())),
+                _ => None,
+            };
+            if let Some(source) = shipment {
+                // One proof per certification; a rerun reuses what earlier runs proved
+                let summary_path = prove.summary.clone()
+                    .unwrap_or_else(|| work_dir.join("shipments").join(format!("{}.json", source.name())));
+                let jobs = config.workers.max_concurrent_proofs.unwrap_or(1);
+                info!("Proving shipment {:?} with {} jobs; summary in {:?}", source.name(), jobs, summary_path);
+                let store = open_store()?;
+                let summary = shipment::prove_shipment(store.as_ref(), &work_dir, &source, prove.mode, prove.force, jobs, &summary_path)?;
+                report::print(&summary, args.format)?;
+                return Ok(match (summary.errors, summary.failed) {
+                    (0, 0) => Outcome::Success,
+                    (0, _) => Outcome::NonCompliant,
+                    _ => Outcome::Incomplete,
+                });
+            }
+
+            let certifications = prove.certs.iter()
+                .map(|path| types::read_certification(path))
+                .collect::<Result<Vec<_>>>()?;
+            // Nothing is proven unless every certification holds up
+            for (path, certification) in prove.certs.iter().zip(&certifications) {
+                let report = validation::ValidationReport::new(path.display().to_string(), certification);
+                if !report.valid {
+                    report::print(&report, args.format)?;
+                    return Ok(Outcome::Invalid);
+                }
+            }
+            if prove.batch {
+                let errors = validation::duplicate_certificates(&certifications);
+                if !errors.is_empty() {
+                    let report = validation::ValidationReport { source: "batch".to_string(), certificate_id: None, valid: false, errors };
+                    report::print(&report, args.format)?;
+                    return Ok(Outcome::Invalid);
+                }
+            }
+            let store = open_store()?;
+
+            if prove.batch {
+                // Prove all certifications in a single aggregated proof
+                info!("Proving a batch of {} certifications", certifications.len());
+                let batch_id = batch::generate_batch_proof(store.as_ref(), &work_dir, &certifications, prove.mode)?;
+                let record = store.get_batch(&batch_id)?
+                    .ok_or_else(|| anyhow::anyhow!("Batch {} missing from store", batch_id))?;
+                let proved = Proved {
+                    proof_id: batch_id,
+                    batch: true,
+                    reused: false,
+                    mode: prove.mode,
+                    program_hash: batch::batch_program_hash().to_string(),
+                    certificates: record.entries.iter()
+                        .map(|entry| ProvedCertificate {
+                            certificate_id: entry.certificate_id.clone(),
+                            overall_compliance: if entry.verdict { "PASS" } else { "FAIL" }.to_string(),
+                        })
+                        .collect(),
+                };
+                report::print(&proved, args.format)?;
+                let compliant = record.entries.iter().all(|entry| entry.verdict);
+                return Ok(if compliant { Outcome::Success } else { Outcome::NonCompliant });
+            }
+
+            let [certification] = certifications.as_slice() else {
+                anyhow::bail!("Pass --batch to prove several certifications together");
+            };
+            info!("Read certification data for material: {}", certification.certificate_id);
+
+            if prove.preflight {
+                let report = circuit::preflight(certification, prove.mode)?;
+                report::print(&report, args.format)?;
+                return Ok(Outcome::from_compliance(&report.overall_compliance));
+            }
+
+            // Generate proof
+            let generated = circuit::generate_proof(store.as_ref(), &work_dir, certification, prove.mode, prove.force)?;
+            if generated.reused {
+                info!("Reused existing proof: {}", generated.proof_id);
+            } else {
+                info!("Generated proof: {}", generated.proof_id);
+            }
+
+            let record = store.get_proof(&generated.proof_id)?
+                .ok_or_else(|| anyhow::anyhow!("Proof {} missing from store", generated.proof_id))?;
+            evm::ensure_setup(store.as_ref(), &work_dir, record.mode);
+            let verification = circuit::verify_proof(&record, certification, &work_dir)?;
+            let proved = Proved {
+                proof_id: generated.proof_id,
+                batch: false,
+                reused: generated.reused,
+                mode: record.mode,
+                program_hash: record.program_hash.unwrap_or_default(),
+                certificates: vec![ProvedCertificate {
+                    certificate_id: certification.certificate_id.clone(),
+                    overall_compliance: verification.overall_compliance.clone(),
+                }],
+            };
+            report::print(&proved, args.format)?;
+            Ok(Outcome::from_compliance(&verification.overall_compliance))
+        }
+        Command::Verify(verify) => {
+            let cert = verify.cert.as_deref().map(types::read_certification).transpose()?;
+
+            if let (Some(public), Some(vkey)) = (&verify.legacy_public, &verify.legacy_vkey) {
+                // A proof from the legacy snarkjs pipeline
+                let bundle = legacy::read_legacy_bundle(Path::new(&verify.proof), public, vkey, verify.legacy_report.as_deref())?;
+                let result = legacy::verify_legacy_proof(&bundle)?;
+                report::print(&result, args.format)?;
+                return Ok(Outcome::from_verification(result.zkp_info.verified, &result.overall_compliance));
+            }
+
+            let source = ProofSource::load(&verify.proof, open_store)?;
+            let checked = match source {
+                ProofSource::Proof(record) => {
+                    let cert = cert.unwrap_or_else(|| record.certification.clone());
+                    if !circuit::covers(&record, &cert)? {
+                        Err(anyhow::anyhow!("Proof {} does not cover this certification", record.id))
+                    } else {
+                        if record.mode == types::ProvingMode::Evm {
+                            evm::ensure_setup(open_store()?.as_ref(), &work_dir, record.mode);
+                        }
+                        circuit::verify_proof(&record, &cert, &work_dir)
+                            .and_then(|result| {
+                                report::print(&result, args.format)?;
+                                Ok(Outcome::from_verification(result.zkp_info.verified, &result.overall_compliance))
+                            })
+                    }
+                }
+                ProofSource::Bundle(proof_bundle) => {
+                    let record = proof_bundle.payload.record()?;
+                    match cert {
+                        Some(cert) if !circuit::covers(&record, &cert)? => {
+                            Err(anyhow::anyhow!("Proof {} does not cover this certification", record.id))
+                        }
+                        _ => trusted_issuers(&config, &output, &verify.issuer_key)
+                            .and_then(|trusted| bundle::verify_bundle(&proof_bundle, &trusted, verify.program_hash.as_deref()))
+                            .and_then(|checked| {
+                                report::print(&checked, args.format)?;
+                                Ok(Outcome::from_verification(checked.verification.zkp_info.verified, &checked.verification.overall_compliance))
+                            }),
+                    }
+                }
+                ProofSource::Batch(record) => {
+                    let certificate_id = verify.certificate.clone()
+                        .or_else(|| cert.as_ref().map(|cert| cert.certificate_id.clone()))
+                        .ok_or_else(|| anyhow::anyhow!("Pass --certificate or --cert to say which certificate of batch {} to check", record.id))?;
+                    let stored = record.certifications.iter().find(|stored| stored.certificate_id == certificate_id);
+                    let differs = match (&cert, stored) {
+                        (Some(cert), Some(stored)) => serde_json::to_value(cert)? != serde_json::to_value(stored)?,
+                        _ => false,
+                    };
+                    if differs {
+                        Err(anyhow::anyhow!("Batch {} does not cover this certification", record.id))
+                    } else {
+                        if record.mode == types::ProvingMode::Evm {
+                            evm::ensure_setup(open_store()?.as_ref(), &work_dir, record.mode);
+                        }
+                        batch::verify_batch_inclusion(&record, &certificate_id, &work_dir)
+                            .and_then(|inclusion| {
+                                report::print(&inclusion, args.format)?;
+                                Ok(Outcome::from_verification(inclusion.verified, &inclusion.overall_compliance))
+                            })
+                    }
+                }
+            };
+
+            match checked {
+                Ok(outcome) => Ok(outcome),
+                Err(reason) => {
+                    report::print(&Rejection::new(&verify.proof, format!("{:#}", reason)), args.format)?;
+                    Ok(Outcome::Rejected)
+                }
+            }
+        }
+        Command::Preflight { cert, mode } => {
+            let certification = types::read_certification(&cert)?;
+            let report = validation::ValidationReport::new(cert.display().to_string(), &certification);
+            if !report.valid {
+                report::print(&report, args.format)?;
+                return Ok(Outcome::Invalid);
+            }
+            let report = circuit::preflight(&certification, mode)?;
+            report::print(&report, args.format)?;
+            Ok(Outcome::from_compliance(&report.overall_compliance))
+        }
+        Command::Inspect { proof } => {
+            let inspection = match ProofSource::load(&proof, open_store)? {
+                ProofSource::Proof(record) => inspect::inspect_proof(&record),
+                ProofSource::Batch(record) => inspect::inspect_batch(&record),
+                ProofSource::Bundle(proof_bundle) => inspect::inspect_bundle(&proof_bundle)?,
+            };
+            report::print(&inspection, args.format)?;
+            Ok(Outcome::Success)
+        }
+        Command::ExportEvm { proof_id } => {
+            // Export an on-chain verifier for an existing evm proof
+            let store = open_store()?;
+            let record = store.get_proof(&proof_id)?
+                .ok_or_else(|| anyhow::anyhow!("Proof {} not found", proof_id))?;
+            evm::ensure_setup(store.as_ref(), &work_dir, record.mode);
+            let export = evm::export_verifier(&work_dir, &record)?;
+            let export_dir = evm::write_export(&export, &work_dir)?;
+            println!("{}", export_dir.display());
+            Ok(Outcome::Success)
+        }
+        Command::Validate { .. } | Command::Config { .. } => unreachable!("handled before the runtime starts"),
+    }
+}
@@ -0,0 +1,90 @@
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use std::sync::LazyLock;
+
+/// Registry backing the `/metrics` endpoint
+pub static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+/// API requests received, by route
+pub static API_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("pico_api_requests_total", "API requests received by route"),
+        &["route"],
+    ))
+});
+
+/// Proof generation failures, by the phase that failed
+pub static PROOF_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("pico_proof_failures_total", "Proof generation failures by reason"),
+        &["reason"],
+    ))
+});
+
+/// End-to-end proving time, by proving mode
+pub static PROVING_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new("pico_proving_duration_seconds", "Time spent generating a proof")
+            .buckets(vec![1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 3600.0]),
+        &["mode"],
+    ))
+});
+
+/// Proof requests accepted but not yet finished
+pub static PROOF_QUEUE_DEPTH: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new("pico_proof_queue_depth", "Proof requests in flight"))
+});
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(metric: prometheus::Result<T>) -> T {
+    let metric = metric.expect("metric definition is valid");
+    REGISTRY.register(Box::new(metric.clone())).expect("metric is registered once");
+    metric
+}
+
+/// Count a request against its route
+pub fn record_request(route: &str) {
+    API_REQUESTS.with_label_values(&[route]).inc();
+}
+
+/// Count a failed proof against the phase that failed
+pub fn record_failure(reason: &str) {
+    PROOF_FAILURES.with_label_values(&[reason]).inc();
+}
+
+/// Keeps the queue depth gauge raised while a proof request is being handled
+pub struct QueueGuard;
+
+impl QueueGuard {
+    pub fn enter() -> Self {
+        PROOF_QUEUE_DEPTH.inc();
+        QueueGuard
+    }
+}
+
+impl Drop for QueueGuard {
+    fn drop(&mut self) {
+        PROOF_QUEUE_DEPTH.dec();
+    }
+}
+
+/// Peak resident set size of this process in bytes, where the platform reports it
+pub fn peak_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Render every registered metric in the Prometheus text format
+pub fn render() -> String {
+    // Touch the lazily created metrics so they appear before their first sample
+    LazyLock::force(&API_REQUESTS);
+    LazyLock::force(&PROOF_FAILURES);
+    LazyLock::force(&PROVING_DURATION);
+    LazyLock::force(&PROOF_QUEUE_DEPTH);
+
+    let mut buffer = Vec::new();
+    if let Err(err) = TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer) {
+        tracing::error!("Failed to encode metrics: {:?}", err);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
@@ -0,0 +1,145 @@
+//! Guest program versions whose proofs can still be verified.
+//!
+//! The guests built into this binary are always known. Earlier releases are listed in the
+//! configuration so their proofs stay verifiable after the guest changes; each entry says how
+//! its public values are laid out, so the matching decoder is used.
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use crate::batch;
+use crate::circuit;
+use crate::guest;
+use crate::types::ProofId;
+
+/// How a guest lays out the public values it commits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputLayout {
+    /// One byte for overall compliance, then one per property in hash map order; the original
+    /// guest, whose per-property verdicts cannot be attributed to properties
+    #[serde(rename = "compliance-v0")]
+    ComplianceV0,
+    /// One byte for overall compliance, then one per numeric property, public before private and by name
+    #[serde(rename = "compliance-v1")]
+    ComplianceV1,
+    /// A bincode `BatchOutput` with the Merkle root and one verdict per certificate
+    #[serde(rename = "batch-v1")]
+    BatchV1,
+}
+
+impl OutputLayout {
+    pub fn kind(self) -> GuestKind {
+        match self {
+            OutputLayout::ComplianceV0 | OutputLayout::ComplianceV1 => GuestKind::Certificate,
+            OutputLayout::BatchV1 => GuestKind::Batch,
+        }
+    }
+}
+
+/// Which of the two guests a program is a version of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestKind {
+    Certificate,
+    Batch,
+}
+
+impl GuestKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            GuestKind::Certificate => "certificate",
+            GuestKind::Batch => "batch",
+        }
+    }
+}
+
+/// A released build of a guest program
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GuestVersion {
+    /// Hex SHA-256 of the ELF
+    pub program_hash: String,
+    pub version: String,
+    pub output_layout: OutputLayout,
+    /// Digest of the guest's RISC-V verifying key, which EVM proofs commit to; without it EVM
+    /// proofs are checked against the digest the prover reported when it proved the guest here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verifying_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub released: Option<NaiveDate>,
+    /// Proofs are still accepted but reported as coming from an outdated guest
+    #[serde(default)]
+    pub deprecated: bool,
+    /// Proofs whose records name no program came from this release, which predates program
+    /// hashes being recorded; at most one release of each guest may say so
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub untagged: bool,
+}
+
+/// Earlier versions from the configuration
+static CONFIGURED: OnceLock<Vec<GuestVersion>> = OnceLock::new();
+
+/// Accept proofs of these guest versions too; only the first call has any effect
+pub fn use_guest_versions(versions: Vec<GuestVersion>) {
+    let _ = CONFIGURED.set(versions);
+}
+
+/// The guests this build proves with
+fn running() -> [GuestVersion; 2] {
+    let built_in = |hash: &str, built_in_hash: &str, version: &str, output_layout| GuestVersion {
+        program_hash: hash.to_string(),
+        // A guest loaded from the configuration is not a release of the bundled sources
+        version: if hash == built_in_hash { version.to_string() } else { "unreleased".to_string() },
+        output_layout,
+        verifying_key: None,
+        released: None,
+        deprecated: false,
+        untagged: false,
+    };
+    [
+        built_in(circuit::guest_program_hash(), guest::PROGRAM_HASH, guest::VERSION, guest::OUTPUT_LAYOUT),
+        built_in(batch::batch_program_hash(), guest::BATCH_PROGRAM_HASH, guest::BATCH_VERSION, guest::BATCH_OUTPUT_LAYOUT),
+    ]
+}
+
+/// Every known version; configured entries take precedence over the running guests
+pub fn versions() -> Vec<GuestVersion> {
+    let mut versions = CONFIGURED.get().cloned().unwrap_or_default();
+    for version in running() {
+        if !versions.iter().any(|known| known.program_hash == version.program_hash) {
+            versions.push(version);
+        }
+    }
+    versions
+}
+
+/// The release of `kind` that proofs from before program hashes were recorded came from, if
+/// the configuration names one; the running guests lay out their output differently
+fn untagged(kind: GuestKind) -> Option<GuestVersion> {
+    CONFIGURED.get()?.iter()
+        .find(|version| version.untagged && version.output_layout.kind() == kind)
+        .cloned()
+}
+
+/// The version of `kind` a proof was generated by, refusing proofs no known guest produced
+pub fn resolve(proof_id: &ProofId, program_hash: Option<&str>, kind: GuestKind) -> Result<GuestVersion> {
+    let version = match program_hash {
+        Some(program_hash) => versions().into_iter()
+            .find(|version| version.program_hash.eq_ignore_ascii_case(program_hash))
+            .ok_or_else(|| anyhow::anyhow!("Proof {} was generated by guest program {}, which is not a known version", proof_id, program_hash))?,
+        None => untagged(kind).ok_or_else(|| anyhow::anyhow!(
+            "Proof {} does not record its guest program; generate it again, or list the release it came from as untagged",
+            proof_id
+        ))?,
+    };
+    if version.output_layout.kind() != kind {
+        anyhow::bail!(
+            "Proof {} was generated by {} guest {}, not a {} guest",
+            proof_id, version.output_layout.kind().label(), version.version, kind.label()
+        );
+    }
+    if version.deprecated {
+        tracing::warn!("Proof {} was generated by deprecated guest version {}", proof_id, version.version);
+    }
+    Ok(version)
+}
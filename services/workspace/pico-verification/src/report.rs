@@ -0,0 +1,274 @@
+//! Printing command results for people or for scripts.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+use crate::bundle::BundleVerification;
+use crate::inspect::{DecodedOutputs, Inspection};
+use crate::shipment::ShipmentSummary;
+use crate::types::{BatchInclusionResult, PreflightReport, ProgramInfo, PropertyResult, VerificationResult};
+use crate::validation::ValidationReport;
+
+/// How command results are written to stdout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned text for reading in a terminal
+    #[default]
+    Table,
+    /// Pretty-printed JSON, the same documents the API returns
+    Json,
+}
+
+/// A result as named fields, optionally followed by rows under column headings
+#[derive(Debug, Default)]
+pub struct Table {
+    fields: Vec<(&'static str, String)>,
+    columns: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn field(mut self, name: &'static str, value: impl fmt::Display) -> Self {
+        self.fields.push((name, value.to_string()));
+        self
+    }
+
+    pub fn columns(mut self, columns: &[&'static str]) -> Self {
+        self.columns = columns.to_vec();
+        self
+    }
+
+    pub fn row(mut self, cells: Vec<String>) -> Self {
+        self.rows.push(cells);
+        self
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name_width = self.fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, value) in &self.fields {
+            writeln!(f, "{:width$}  {}", name, value, width = name_width)?;
+        }
+        if self.columns.is_empty() || self.rows.is_empty() {
+            return Ok(());
+        }
+        if !self.fields.is_empty() {
+            writeln!(f)?;
+        }
+
+        let mut widths: Vec<usize> = self.columns.iter().map(|column| column.len()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let line = |f: &mut fmt::Formatter<'_>, cells: Vec<&str>| {
+            let padded: Vec<_> = cells.iter().zip(&widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect();
+            writeln!(f, "{}", padded.join("  ").trim_end())
+        };
+        line(f, self.columns.clone())?;
+        for row in &self.rows {
+            line(f, row.iter().map(String::as_str).collect())?;
+        }
+        Ok(())
+    }
+}
+
+/// Results that can be shown as a [`Table`]
+pub trait Tabular {
+    fn table(&self) -> Table;
+}
+
+/// Write `value` to stdout in `format`
+pub fn print<T: Serialize + Tabular>(value: &T, format: OutputFormat) -> Result<()> {
+    // Unlike print!, a closed pipe such as `| head` is an error rather than a panic
+    let mut stdout = std::io::stdout().lock();
+    match format {
+        OutputFormat::Table => write!(stdout, "{}", value.table())?,
+        OutputFormat::Json => writeln!(stdout, "{}", serde_json::to_string_pretty(value)?)?,
+    }
+    Ok(())
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+fn program(program: Option<&ProgramInfo>) -> String {
+    match program {
+        Some(program) if program.deprecated => format!("{} ({}, deprecated)", program.version, program.program_hash),
+        Some(program) => format!("{} ({})", program.version, program.program_hash),
+        None => "unknown".to_string(),
+    }
+}
+
+/// JSON values without the quotes around strings
+fn plain(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn property_rows(table: Table, properties: &mut [&PropertyResult]) -> Table {
+    properties.sort_by(|a, b| (a.is_private, &a.property).cmp(&(b.is_private, &b.property)));
+    let mut table = table.columns(&["PROPERTY", "VALUE", "THRESHOLDS", "VISIBILITY", "RESULT"]);
+    for prop in properties.iter() {
+        let thresholds = match (&prop.thresholds["min"], &prop.thresholds["max"]) {
+            (serde_json::Value::Null, serde_json::Value::Null) => plain(&prop.thresholds),
+            (min, max) => format!("{} .. {}", min, max),
+        };
+        table = table.row(vec![
+            prop.property.clone(),
+            plain(&prop.value),
+            thresholds,
+            if prop.is_private { "private" } else { "public" }.to_string(),
+            if prop.compliant { "pass" } else { "fail" }.to_string(),
+        ]);
+    }
+    table
+}
+
+impl Tabular for VerificationResult {
+    fn table(&self) -> Table {
+        let summary = &self.compliance_summary;
+        let table = Table::new()
+            .field("Certificate", &self.material_id)
+            .field("Material", format!("{} ({})", self.material_type, self.standard))
+            .field("Compliance", &self.overall_compliance)
+            .field("Properties", format!("{} of {} passing", summary.passing_properties, summary.total_properties))
+            .field("Proof", format!("{} [{}]", self.zkp_info.proof, self.zkp_info.proof_type))
+            .field("Final proof", yes_no(self.zkp_info.verified))
+            .field("Program", program(self.zkp_info.program.as_ref()));
+        property_rows(table, &mut self.properties.values().collect::<Vec<_>>())
+    }
+}
+
+impl Tabular for BundleVerification {
+    fn table(&self) -> Table {
+        let mut table = self.verification.table();
+        table.fields.push(("Issuer", format!("{} (trusted)", self.issuer)));
+        table.fields.push(("Program pinned", yes_no(self.program_pinned).to_string()));
+        table
+    }
+}
+
+impl Tabular for BatchInclusionResult {
+    fn table(&self) -> Table {
+        Table::new()
+            .field("Batch", &self.batch_id)
+            .field("Certificate", format!("{} (entry {})", self.certificate_id, self.index))
+            .field("Included", yes_no(self.included))
+            .field("Compliance", &self.overall_compliance)
+            .field("Merkle root", &self.merkle_root)
+            .field("Proof", &self.proof_type)
+            .field("Final proof", yes_no(self.verified))
+            .field("Program", program(Some(&self.program)))
+    }
+}
+
+impl Tabular for PreflightReport {
+    fn table(&self) -> Table {
+        let table = Table::new()
+            .field("Certificate", &self.material_id)
+            .field("Mode", self.mode.label())
+            .field("Compliance", &self.overall_compliance)
+            .field("Cycles", self.cycles)
+            .field("Emulation", format!("{} ms", self.emulation_ms))
+            .field("Estimated proving", format!("{:.0} s", self.estimated_proving_seconds))
+            .field("Estimated memory", format!("{} MB", self.estimated_memory_mb));
+        property_rows(table, &mut self.properties.values().collect::<Vec<_>>())
+    }
+}
+
+impl Tabular for Inspection {
+    fn table(&self) -> Table {
+        let mut table = Table::new()
+            .field("Kind", self.kind.label())
+            .field("Id", &self.id)
+            .field("Mode", self.mode.label())
+            .field("Proved at", &self.proved_at)
+            .field("Certificates", self.certificates.join(", "))
+            .field("Program hash", self.program_hash.as_deref().unwrap_or("not recorded"))
+            .field("Program", program(self.program.as_ref()))
+            .field("Public values", &self.public_values);
+        if let Some(issuer) = &self.issuer {
+            table = table.field("Issuer", format!("{} (signature not checked)", issuer));
+        }
+        if let Some(metrics) = &self.metrics {
+            table = table
+                .field("Cycles", metrics.cycles)
+                .field("Proving", format!("{} ms", metrics.proving_ms))
+                .field("Proof size", format!("{} bytes", metrics.proof_size_bytes));
+        }
+        if let Some(reason) = &self.undecoded {
+            table = table.field("Outputs", format!("not decoded: {}", reason));
+        }
+        match &self.outputs {
+            Some(DecodedOutputs::Certificate { overall_compliance, properties }) => {
+                let table = table.field("Compliance", overall_compliance);
+                property_rows(table, &mut properties.iter().collect::<Vec<_>>())
+            }
+            Some(DecodedOutputs::Batch { merkle_root, verdicts }) => {
+                let mut table = table.field("Merkle root", merkle_root).columns(&["INDEX", "CERTIFICATE", "COMPLIANCE"]);
+                for verdict in verdicts {
+                    table = table.row(vec![verdict.index.to_string(), verdict.certificate_id.clone(), verdict.overall_compliance.clone()]);
+                }
+                table
+            }
+            None => table,
+        }
+    }
+}
+
+impl Tabular for ValidationReport {
+    fn table(&self) -> Table {
+        let table = Table::new()
+            .field("Certification", &self.source)
+            .field("Certificate", self.certificate_id.as_deref().unwrap_or("-"))
+            .field("Valid", yes_no(self.valid));
+        let mut table = table.columns(&["FIELD", "PROBLEM"]);
+        for error in &self.errors {
+            let field = if error.field.is_empty() { "-" } else { &error.field };
+            table = table.row(vec![field.to_string(), error.message.clone()]);
+        }
+        table
+    }
+}
+
+impl Tabular for ShipmentSummary {
+    fn table(&self) -> Table {
+        let mut table = Table::new()
+            .field("Shipment", &self.shipment)
+            .field("Mode", self.mode.label())
+            .field("Certificates", self.certificates.len())
+            .field("Passed", self.passed)
+            .field("Failed", self.failed)
+            .field("Errors", self.errors)
+            .columns(&["FILE", "CERTIFICATE", "STATUS", "PROOF", "DETAIL"]);
+        for cert in &self.certificates {
+            let file = cert.file.file_name().unwrap_or(cert.file.as_os_str()).to_string_lossy();
+            let detail = match &cert.error {
+                Some(error) => error.clone(),
+                None if cert.reused => "reused".to_string(),
+                None => format!("{} ms", cert.duration_ms),
+            };
+            table = table.row(vec![
+                file.into_owned(),
+                cert.certificate_id.clone().unwrap_or_else(|| "-".to_string()),
+                cert.status.label().to_string(),
+                cert.proof_id.as_ref().map(ToString::to_string).unwrap_or_else(|| "-".to_string()),
+                detail,
+            ]);
+        }
+        table
+    }
+}
@@ -0,0 +1,153 @@
+//! JSON Schemas of the documents integrators exchange with us, generated from the Rust types.
+//!
+//! The published schemas are kept in `schemas/` next to this crate, one file per type and
+//! version. `tests/schema.rs` fails when the types drift from them, so a change to the types
+//! comes with a new schema file, and with a new [`SCHEMA_VERSION`] when old documents stop
+//! being accepted.
+
+use jsonschema::error::ValidationErrorKind;
+use jsonschema::ValidationError;
+use jsonschema::paths::Location;
+use schemars::JsonSchema;
+use serde_json::Value;
+use crate::types::{MaterialCertification, VerificationResult};
+use crate::validation::FieldError;
+
+/// Version of the published schemas
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Documents with a published schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaType {
+    MaterialCertification,
+    VerificationResult,
+}
+
+impl SchemaType {
+    pub const ALL: [SchemaType; 2] = [SchemaType::MaterialCertification, SchemaType::VerificationResult];
+
+    /// Name in `/api/schema/{name}` and in the schema's file name
+    pub fn name(self) -> &'static str {
+        match self {
+            SchemaType::MaterialCertification => "material-certification",
+            SchemaType::VerificationResult => "verification-result",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        SchemaType::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// File the schema is published as in `schemas/`
+    pub fn file_name(self) -> String {
+        format!("{}.v{}.json", self.name(), SCHEMA_VERSION)
+    }
+
+    /// The schema as published, identified by name and version
+    pub fn schema(self) -> Value {
+        let mut schema = match self {
+            SchemaType::MaterialCertification => schema_of::<MaterialCertification>(),
+            SchemaType::VerificationResult => schema_of::<VerificationResult>(),
+        };
+        schema["$id"] = format!("urn:material-pico-verify:schema:{}:v{}", self.name(), SCHEMA_VERSION).into();
+        schema
+    }
+}
+
+/// JSON Schema (draft 2020-12) of the documents `T` is read from
+pub fn schema_of<T: JsonSchema>() -> Value {
+    schemars::schema_for!(T).to_value()
+}
+
+/// Checks documents against the schema of one type, before they are deserialised
+pub struct SchemaValidator(jsonschema::Validator);
+
+impl SchemaValidator {
+    pub fn of<T: JsonSchema>() -> Self {
+        SchemaValidator(jsonschema::validator_for(&schema_of::<T>()).expect("generated schemas are valid"))
+    }
+
+    /// Every place `instance` departs from the schema, by field
+    pub fn check(&self, instance: &Value) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        for error in self.0.iter_errors(instance) {
+            field_errors(instance, &error, &mut errors);
+        }
+        errors
+    }
+}
+
+fn field_errors(instance: &Value, error: &ValidationError<'_>, errors: &mut Vec<FieldError>) {
+    match error.kind() {
+        // Reported on the missing field rather than the object missing it
+        ValidationErrorKind::Required { property } => {
+            let property = property.as_str().map_or_else(|| property.to_string(), str::to_string);
+            let parent = field_path(instance, error.instance_path());
+            let field = if parent.is_empty() { property } else { format!("{}.{}", parent, property) };
+            errors.push(FieldError::new(field, "is required"));
+        }
+        // Enums with documented variants are one constant per variant; name them all
+        ValidationErrorKind::AnyOf { context } | ValidationErrorKind::OneOfNotValid { context }
+            if !context.is_empty() && context.iter().all(|alternative| constant(alternative).is_some()) =>
+        {
+            let allowed: Vec<_> = context.iter().filter_map(|alternative| constant(alternative)).map(Value::to_string).collect();
+            errors.push(FieldError::new(
+                field_path(instance, error.instance_path()),
+                format!("{} is not one of {}", error.instance(), allowed.join(", ")),
+            ));
+        }
+        // Optional fields are "the type or null"; a value that is present is wrong for the type's reasons
+        ValidationErrorKind::AnyOf { context } => {
+            let mut alternatives = context.iter()
+                .filter(|alternative| !matches!(alternative.as_slice(), [only] if is_type_mismatch(only, error)));
+            match (alternatives.next(), alternatives.next()) {
+                (Some(alternative), None) => {
+                    for error in alternative {
+                        field_errors(instance, error, errors);
+                    }
+                }
+                _ => errors.push(FieldError::new(field_path(instance, error.instance_path()), error.to_string())),
+            }
+        }
+        _ => errors.push(FieldError::new(field_path(instance, error.instance_path()), error.to_string())),
+    }
+}
+
+/// The value an alternative required, when all it failed on is a `const`
+fn constant<'a>(alternative: &'a [ValidationError<'_>]) -> Option<&'a Value> {
+    match alternative {
+        [only] => match only.kind() {
+            ValidationErrorKind::Constant { expected_value } => Some(expected_value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `error` only says the value at the same place as `outer` has the wrong type
+fn is_type_mismatch(error: &ValidationError<'_>, outer: &ValidationError<'_>) -> bool {
+    matches!(error.kind(), ValidationErrorKind::Type { .. }) && error.instance_path() == outer.instance_path()
+}
+
+/// Dotted path of a location, with array positions in brackets like `certifications[1].batch`
+fn field_path(instance: &Value, location: &Location) -> String {
+    let mut path = String::new();
+    let mut current = Some(instance);
+    for segment in location.segments() {
+        let segment = segment.to_string();
+        current = match current {
+            Some(Value::Array(items)) => {
+                path.push_str(&format!("[{}]", segment));
+                segment.parse().ok().and_then(|index: usize| items.get(index))
+            }
+            value => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(&segment);
+                value.and_then(|value| value.get(&segment))
+            }
+        };
+    }
+    path
+}
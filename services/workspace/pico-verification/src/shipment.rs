@@ -0,0 +1,252 @@
+//! Proving every certification of a shipment, one proof per certificate.
+//!
+//! A shipment is a folder of certification files or a CSV manifest listing them. Proofs are
+//! content-addressed, so running a shipment again picks up finished proofs from the store and
+//! only proves what is missing or failed before.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use crate::circuit;
+use crate::evm;
+use crate::store::ProofStore;
+use crate::types::{self, ProofId, ProvingMode};
+use crate::validation;
+
+/// Where the certifications of a shipment are listed
+#[derive(Debug, Clone)]
+pub enum ShipmentSource {
+    /// Every `*.json` file directly inside the folder
+    Dir(PathBuf),
+    /// A CSV file with a `file` column, relative to the manifest's folder
+    Manifest(PathBuf),
+}
+
+impl ShipmentSource {
+    /// Short name for the shipment, used to name its summary
+    pub fn name(&self) -> String {
+        let path = match self {
+            ShipmentSource::Dir(path) | ShipmentSource::Manifest(path) => path,
+        };
+        let name = match self {
+            ShipmentSource::Dir(_) => path.file_name(),
+            ShipmentSource::Manifest(_) => path.file_stem(),
+        };
+        name.map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| "shipment".to_string())
+    }
+
+    /// The certification files in the order they are listed
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        match self {
+            ShipmentSource::Dir(dir) => {
+                let mut files = Vec::new();
+                for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+                    let path = entry?.path();
+                    if path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+                        files.push(path);
+                    }
+                }
+                files.sort();
+                Ok(files)
+            }
+            ShipmentSource::Manifest(manifest) => {
+                #[derive(Deserialize)]
+                struct Row {
+                    file: PathBuf,
+                }
+
+                let base = manifest.parent().unwrap_or(Path::new("."));
+                let mut reader = csv::ReaderBuilder::new()
+                    .trim(csv::Trim::All)
+                    .from_path(manifest)
+                    .with_context(|| format!("Failed to read manifest {:?}", manifest))?;
+                reader.deserialize::<Row>()
+                    .map(|row| Ok(base.join(row.with_context(|| format!("Invalid manifest {:?}", manifest))?.file)))
+                    .collect()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CertificateStatus {
+    /// Not proven yet; only seen in the summary of a run still in progress or interrupted
+    Pending,
+    Pass,
+    Fail,
+    Error,
+}
+
+impl CertificateStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            CertificateStatus::Pending => "pending",
+            CertificateStatus::Pass => "pass",
+            CertificateStatus::Fail => "fail",
+            CertificateStatus::Error => "error",
+        }
+    }
+}
+
+/// How one certification of a shipment fared
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateOutcome {
+    pub file: PathBuf,
+    pub certificate_id: Option<String>,
+    pub status: CertificateStatus,
+    pub proof_id: Option<ProofId>,
+    /// Taken from the store, proven by an earlier run
+    pub reused: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl CertificateOutcome {
+    fn pending(file: &Path) -> Self {
+        CertificateOutcome {
+            file: file.to_path_buf(),
+            certificate_id: None,
+            status: CertificateStatus::Pending,
+            proof_id: None,
+            reused: false,
+            error: None,
+            duration_ms: 0,
+        }
+    }
+}
+
+fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> &str {
+    panic.downcast_ref::<&str>().copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Result of proving a shipment, kept up to date on disk while it runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShipmentSummary {
+    pub shipment: String,
+    pub mode: ProvingMode,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub passed: usize,
+    pub failed: usize,
+    pub errors: usize,
+    pub certificates: Vec<CertificateOutcome>,
+}
+
+impl ShipmentSummary {
+    fn count(&mut self) {
+        let count = |status| self.certificates.iter().filter(|cert| cert.status == status).count();
+        (self.passed, self.failed, self.errors) =
+            (count(CertificateStatus::Pass), count(CertificateStatus::Fail), count(CertificateStatus::Error));
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        // Written in place of the previous version, so an interrupted run never leaves half a file
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Prove each certification of `source` with up to `jobs` proofs at a time, rewriting the summary
+/// at `summary_path` after every certificate. Failures of single certificates are recorded, not returned.
+pub fn prove_shipment(
+    store: &dyn ProofStore,
+    work_dir: &Path,
+    source: &ShipmentSource,
+    mode: ProvingMode,
+    force: bool,
+    jobs: usize,
+    summary_path: &Path,
+) -> Result<ShipmentSummary> {
+    let files = source.files()?;
+    if let Some(dir) = summary_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let summary = Mutex::new(ShipmentSummary {
+        shipment: source.name(),
+        mode,
+        started_at: chrono::Utc::now().to_rfc3339(),
+        finished_at: None,
+        passed: 0,
+        failed: 0,
+        errors: 0,
+        certificates: files.iter().map(|file| CertificateOutcome::pending(file)).collect(),
+    });
+    summary.lock().unwrap().write(summary_path)?;
+
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, files.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(file) = files.get(index) else { break };
+                // The prover panics on some malformed inputs; that should only fail this certificate
+                let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| prove_certificate(store, work_dir, file, mode, force)))
+                    .unwrap_or_else(|panic| CertificateOutcome {
+                        status: CertificateStatus::Error,
+                        error: Some(format!("Prover panicked: {}", panic_message(&panic))),
+                        ..CertificateOutcome::pending(file)
+                    });
+                match &outcome.error {
+                    Some(err) => tracing::warn!("{}: {}", file.display(), err),
+                    None => tracing::info!("{}: {:?}", file.display(), outcome.status),
+                }
+
+                let mut summary = summary.lock().unwrap();
+                summary.certificates[index] = outcome;
+                summary.count();
+                if let Err(err) = summary.write(summary_path) {
+                    tracing::error!("Failed to update shipment summary {:?}: {:#}", summary_path, err);
+                }
+            });
+        }
+    });
+
+    let mut summary = summary.into_inner().unwrap();
+    summary.finished_at = Some(chrono::Utc::now().to_rfc3339());
+    summary.write(summary_path)?;
+    Ok(summary)
+}
+
+fn prove_certificate(store: &dyn ProofStore, work_dir: &Path, file: &Path, mode: ProvingMode, force: bool) -> CertificateOutcome {
+    let started = Instant::now();
+    let mut outcome = CertificateOutcome::pending(file);
+
+    let proven = (|| {
+        let cert = types::read_certification(file)?;
+        outcome.certificate_id = Some(cert.certificate_id.clone());
+        let problems = validation::validate(&cert);
+        if !problems.is_empty() {
+            let problems: Vec<_> = problems.iter().map(ToString::to_string).collect();
+            anyhow::bail!("Invalid certification: {}", problems.join("; "));
+        }
+
+        let generated = circuit::generate_proof(store, work_dir, &cert, mode, force)?;
+        outcome.proof_id = Some(generated.proof_id.clone());
+        outcome.reused = generated.reused;
+        let record = store.get_proof(&generated.proof_id)?
+            .ok_or_else(|| anyhow::anyhow!("Proof {} missing from store", generated.proof_id))?;
+        evm::ensure_setup(store, work_dir, record.mode);
+        circuit::verify_proof(&record, &cert, work_dir)
+    })();
+
+    match proven {
+        Ok(result) if result.overall_compliance == "PASS" => outcome.status = CertificateStatus::Pass,
+        Ok(_) => outcome.status = CertificateStatus::Fail,
+        Err(err) => {
+            outcome.status = CertificateStatus::Error;
+            outcome.error = Some(format!("{:#}", err));
+        }
+    }
+    outcome.duration_ms = started.elapsed().as_millis() as u64;
+    outcome
+}
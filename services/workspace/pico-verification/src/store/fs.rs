@@ -0,0 +1,134 @@
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use super::{Job, ProofPage, ProofQuery, ProofStore};
+use crate::batch::BATCH_DIR;
+use crate::types::{BatchProofRecord, ProofId, ProofRecord, ProofSummary};
+
+/// Jobs are kept apart from records so listing the output directory stays readable
+const JOBS_DIR: &str = "jobs";
+
+/// The original layout: `<id>.json` proofs, `batches/<id>.json` and `jobs/<id>.json`
+pub struct FsStore {
+    root: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(root: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&root)?;
+        Ok(FsStore { root })
+    }
+
+    fn read<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+        write_atomic(path, serde_json::to_string_pretty(value)?.as_bytes())
+    }
+
+    /// `false` if the file was already gone
+    fn remove(path: &Path) -> Result<bool> {
+        match std::fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Every proof record in the root; other JSON files in the output directory are skipped
+    fn summaries(&self) -> Result<Vec<ProofSummary>> {
+        let mut summaries = Vec::new();
+        for entry in std::fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            let is_record = path.extension().is_some_and(|ext| ext == "json")
+                && path.file_stem().and_then(|stem| stem.to_str()).is_some_and(|stem| stem.parse::<ProofId>().is_ok());
+            if !is_record {
+                continue;
+            }
+            match Self::read::<ProofRecord>(&path) {
+                Ok(Some(record)) => summaries.push(ProofSummary::from(&record)),
+                Ok(None) => {},
+                Err(err) => tracing::warn!("Skipping unreadable proof record {:?}: {}", path, err),
+            }
+        }
+        Ok(summaries)
+    }
+}
+
+impl ProofStore for FsStore {
+    fn get_proof(&self, id: &ProofId) -> Result<Option<ProofRecord>> {
+        Self::read(&id.record_path(&self.root))
+    }
+
+    fn put_proof(&self, record: &ProofRecord) -> Result<()> {
+        Self::write(&record.id.record_path(&self.root), record)
+    }
+
+    fn list_proofs(&self, query: &ProofQuery) -> Result<ProofPage> {
+        let mut matching: Vec<ProofSummary> = self.summaries()?
+            .into_iter()
+            .filter(|summary| query.matches(summary))
+            .collect();
+        // RFC 3339 timestamps in UTC sort the same as text
+        matching.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| a.id.as_str().cmp(b.id.as_str())));
+
+        let total = matching.len();
+        let items = matching.into_iter().skip(query.offset).take(query.page_size()).collect();
+        Ok(ProofPage { items, total, limit: query.page_size(), offset: query.offset })
+    }
+
+    fn delete_proof(&self, id: &ProofId) -> Result<bool> {
+        let existed = Self::remove(&id.record_path(&self.root))?;
+        Self::remove(&id.record_path(&self.root.join(JOBS_DIR)))?;
+        Ok(existed)
+    }
+
+    fn get_batch(&self, id: &ProofId) -> Result<Option<BatchProofRecord>> {
+        Self::read(&id.record_path(&self.root.join(BATCH_DIR)))
+    }
+
+    fn put_batch(&self, record: &BatchProofRecord) -> Result<()> {
+        Self::write(&record.id.record_path(&self.root.join(BATCH_DIR)), record)
+    }
+
+    fn get_job(&self, id: &ProofId) -> Result<Option<Job>> {
+        Self::read(&id.record_path(&self.root.join(JOBS_DIR)))
+    }
+
+    fn put_job(&self, job: &Job) -> Result<()> {
+        Self::write(&job.id.record_path(&self.root.join(JOBS_DIR)), job)
+    }
+}
+
+/// Write to a temporary file next to `path` and rename it into place, so readers
+/// only ever see the old or the new contents
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = path.parent().ok_or_else(|| anyhow::anyhow!("{:?} has no parent directory", path))?;
+    std::fs::create_dir_all(dir)?;
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = dir.join(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+    let written = std::fs::File::create(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&tmp_path, path));
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(err.into());
+    }
+
+    // Persist the rename itself; not every platform allows syncing a directory
+    if let Ok(dir) = std::fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
@@ -0,0 +1,246 @@
+//! Persistence for proof records, batch records and proving job state.
+
+mod fs;
+mod s3;
+mod sqlite;
+
+pub use fs::FsStore;
+pub use s3::{S3Config, S3Store};
+pub use sqlite::SqliteStore;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::types::{BatchProofRecord, OrgId, ProofId, ProofRecord, ProofSummary};
+
+/// Where proof records and job state are kept.
+///
+/// Proving artifacts (recursion outputs, the shared EVM setup) are made in the working
+/// directory. Stores shared between instances also keep a copy, so an instance can use what
+/// another one made; for the others the working directory is the only copy.
+pub trait ProofStore: Send + Sync {
+    fn get_proof(&self, id: &ProofId) -> Result<Option<ProofRecord>>;
+
+    /// Insert or replace a proof record
+    fn put_proof(&self, record: &ProofRecord) -> Result<()>;
+
+    /// Proofs matching `query`, newest first
+    fn list_proofs(&self, query: &ProofQuery) -> Result<ProofPage>;
+
+    /// Remove a proof record and its job; `false` if there was no such proof
+    fn delete_proof(&self, id: &ProofId) -> Result<bool>;
+
+    fn get_batch(&self, id: &ProofId) -> Result<Option<BatchProofRecord>>;
+
+    /// Insert or replace a batch record
+    fn put_batch(&self, record: &BatchProofRecord) -> Result<()>;
+
+    fn get_job(&self, id: &ProofId) -> Result<Option<Job>>;
+
+    fn put_job(&self, job: &Job) -> Result<()>;
+
+    /// Share the artifacts in `<work_dir>/<name>/` with other instances using this store
+    fn put_artifacts(&self, _work_dir: &Path, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Fetch the artifacts another instance shared under `name` into `<work_dir>/<name>/`;
+    /// `false` if there are none
+    fn get_artifacts(&self, _work_dir: &Path, _name: &str) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Largest page a listing returns, whatever the caller asks for
+pub const MAX_PAGE_SIZE: usize = 200;
+
+fn default_page_size() -> usize {
+    50
+}
+
+/// Filters and paging for listing proofs; every filter that is set must match
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProofQuery {
+    pub certificate_id: Option<String>,
+    pub batch_number: Option<String>,
+    pub manufacturer: Option<String>,
+    /// Only proofs generated at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only proofs generated at or before this time
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default = "default_page_size")]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+impl Default for ProofQuery {
+    fn default() -> Self {
+        ProofQuery {
+            certificate_id: None,
+            batch_number: None,
+            manufacturer: None,
+            from: None,
+            to: None,
+            limit: default_page_size(),
+            offset: 0,
+        }
+    }
+}
+
+impl ProofQuery {
+    pub fn page_size(&self) -> usize {
+        self.limit.clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// Backends that cannot filter natively check each summary with this
+    pub fn matches(&self, summary: &ProofSummary) -> bool {
+        let equals = |filter: &Option<String>, value: &str| filter.as_deref().is_none_or(|f| f == value);
+        let in_range = match DateTime::parse_from_rfc3339(&summary.timestamp) {
+            Ok(ts) => self.from.is_none_or(|from| ts >= from) && self.to.is_none_or(|to| ts <= to),
+            Err(_) => self.from.is_none() && self.to.is_none(),
+        };
+
+        equals(&self.certificate_id, &summary.certificate_id)
+            && equals(&self.batch_number, &summary.batch_number)
+            && equals(&self.manufacturer, &summary.manufacturer)
+            && in_range
+    }
+}
+
+/// One page of a proof listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofPage {
+    pub items: Vec<ProofSummary>,
+    /// Matching proofs across all pages
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// Lifecycle of a proving request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Proving,
+    Completed,
+    Failed,
+}
+
+impl JobState {
+    pub fn label(self) -> &'static str {
+        match self {
+            JobState::Proving => "proving",
+            JobState::Completed => "completed",
+            JobState::Failed => "failed",
+        }
+    }
+}
+
+/// Latest known state of the job producing a proof or batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: ProofId,
+    pub state: JobState,
+    pub updated_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Job {
+    pub fn new(id: &ProofId, state: JobState, error: Option<String>) -> Self {
+        Job {
+            id: id.clone(),
+            state,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+            error,
+        }
+    }
+}
+
+/// Run `work` as the job for `id`, recording whether it is proving, completed or failed
+pub fn track_job<T>(store: &dyn ProofStore, id: &ProofId, work: impl FnOnce() -> Result<T>) -> Result<T> {
+    store.put_job(&Job::new(id, JobState::Proving, None))?;
+    let outcome = work();
+    let job = match &outcome {
+        Ok(_) => Job::new(id, JobState::Completed, None),
+        Err(err) => Job::new(id, JobState::Failed, Some(format!("{:#}", err))),
+    };
+    store.put_job(&job)?;
+    outcome
+}
+
+/// Storage backends that can be selected in configuration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    /// One JSON file per record in the output directory
+    #[default]
+    Fs,
+    /// A single embedded SQLite database
+    Sqlite,
+}
+
+/// Which backend to use and where it keeps its data
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StoreConfig {
+    #[serde(default)]
+    pub backend: StoreBackend,
+    /// Directory for `fs`, database file for `sqlite`; defaults to a location in the output directory
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Keep proof payloads and proving artifacts in object storage, with `backend` holding the records
+    #[serde(default)]
+    pub s3: Option<S3Config>,
+}
+
+impl StoreConfig {
+    pub fn open(&self, output_dir: &Path) -> Result<Arc<dyn ProofStore>> {
+        let primary = self.open_primary(output_dir)?;
+        Ok(match &self.s3 {
+            Some(s3) => {
+                tracing::info!("Storing proof payloads in bucket {} at {}", s3.bucket, s3.endpoint);
+                Arc::new(S3Store::new(s3, primary)?)
+            },
+            None => primary,
+        })
+    }
+
+    /// The same backend, holding only `org`'s data
+    pub fn for_org(&self, org: &OrgId) -> StoreConfig {
+        let path = self.path.as_ref().map(|path| match self.backend {
+            StoreBackend::Fs => org.dir(path),
+            StoreBackend::Sqlite => {
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                org.dir(dir).join(path.file_name().unwrap_or_default())
+            },
+        });
+        let s3 = self.s3.clone().map(|s3| {
+            let prefix = if org.is_default() {
+                s3.prefix
+            } else {
+                format!("{}/orgs/{}", s3.prefix.trim_matches('/'), org).trim_start_matches('/').to_string()
+            };
+            S3Config { prefix, ..s3 }
+        });
+        StoreConfig { backend: self.backend, path, s3 }
+    }
+
+    fn open_primary(&self, output_dir: &Path) -> Result<Arc<dyn ProofStore>> {
+        Ok(match self.backend {
+            StoreBackend::Fs => {
+                let dir = self.path.clone().unwrap_or_else(|| output_dir.to_path_buf());
+                tracing::info!("Using filesystem proof store at {:?}", dir);
+                Arc::new(FsStore::new(dir)?)
+            },
+            StoreBackend::Sqlite => {
+                let path = self.path.clone().unwrap_or_else(|| output_dir.join("proofs.db"));
+                tracing::info!("Using SQLite proof store at {:?}", path);
+                Arc::new(SqliteStore::open(&path)?)
+            },
+        })
+    }
+}
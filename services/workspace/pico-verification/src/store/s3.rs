@@ -0,0 +1,289 @@
+use anyhow::{Context, Result};
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use super::{Job, ProofPage, ProofQuery, ProofStore};
+use crate::types::{BatchProofRecord, EvmProofInputs, ProofId, ProofRecord};
+
+/// Connection settings for an S3-compatible bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3Config {
+    /// e.g. `https://s3.eu-west-1.amazonaws.com` or `http://minio:9000`
+    pub endpoint: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    pub bucket: String,
+    /// Key prefix shared by every object this store writes
+    #[serde(default)]
+    pub prefix: String,
+    /// Taken from the standard AWS environment variables or profile when unset
+    #[serde(default)]
+    pub access_key: Option<String>,
+    #[serde(default)]
+    pub secret_key: Option<String>,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        S3Config {
+            endpoint: String::new(),
+            region: default_region(),
+            bucket: String::new(),
+            prefix: String::new(),
+            access_key: None,
+            secret_key: None,
+        }
+    }
+}
+
+/// The bulky part of a proof or batch record, kept in object storage
+#[derive(Serialize, Deserialize)]
+struct ProofBlob {
+    pv_stream: Option<Vec<u8>>,
+    #[serde(default)]
+    embed_proof: Option<String>,
+    evm_inputs: Option<EvmProofInputs>,
+}
+
+/// Files under `artifacts/<name>/`, listed once they have all been uploaded
+const ARTIFACT_MANIFEST: &str = "manifest.json";
+
+/// Proof payloads and proving artifacts in an S3 bucket shared between instances, with the
+/// records themselves (and so every metadata lookup) left in a primary store
+pub struct S3Store {
+    primary: Arc<dyn ProofStore>,
+    bucket: Box<Bucket>,
+    prefix: String,
+}
+
+impl S3Store {
+    pub fn new(config: &S3Config, primary: Arc<dyn ProofStore>) -> Result<Self> {
+        let region = Region::Custom {
+            region: config.region.clone(),
+            endpoint: config.endpoint.trim_end_matches('/').to_string(),
+        };
+        let credentials = Credentials::new(config.access_key.as_deref(), config.secret_key.as_deref(), None, None, None)?;
+        // Path-style addressing is understood by AWS as well as MinIO and other stand-ins
+        let bucket = Bucket::new(&config.bucket, region, credentials)?.with_path_style();
+
+        Ok(S3Store {
+            primary,
+            bucket,
+            prefix: config.prefix.trim_matches('/').to_string(),
+        })
+    }
+
+    fn key(&self, kind: &str, id: &ProofId) -> String {
+        if self.prefix.is_empty() {
+            format!("{}/{}.json", kind, id)
+        } else {
+            format!("{}/{}/{}.json", self.prefix, kind, id)
+        }
+    }
+
+    fn artifact_key(&self, name: &str, file: &str) -> String {
+        if self.prefix.is_empty() {
+            format!("artifacts/{}/{}", name, file)
+        } else {
+            format!("{}/artifacts/{}/{}", self.prefix, name, file)
+        }
+    }
+
+    fn put_blob(&self, key: &str, blob: &ProofBlob) -> Result<()> {
+        let response = self.bucket.put_object_with_content_type(key, &serde_json::to_vec(blob)?, "application/json")?;
+        if !(200..300).contains(&response.status_code()) {
+            anyhow::bail!("Uploading {} failed with HTTP {}", key, response.status_code());
+        }
+        Ok(())
+    }
+
+    fn delete_blob(&self, key: &str) -> Result<()> {
+        let response = self.bucket.delete_object(key)?;
+        if !(200..300).contains(&response.status_code()) && response.status_code() != 404 {
+            anyhow::bail!("Deleting {} failed with HTTP {}", key, response.status_code());
+        }
+        Ok(())
+    }
+
+    /// `None` when there is no such object, e.g. for records written before S3 was enabled
+    fn get_blob(&self, key: &str) -> Result<Option<ProofBlob>> {
+        let response = self.bucket.get_object(key)?;
+        match response.status_code() {
+            404 => Ok(None),
+            200..=299 => Ok(Some(serde_json::from_slice(response.as_slice())?)),
+            status => anyhow::bail!("Downloading {} failed with HTTP {}", key, status),
+        }
+    }
+
+    /// The files shared under `name`, relative to its directory; `None` if nothing was shared
+    fn artifact_manifest(&self, name: &str) -> Result<Option<Vec<String>>> {
+        let key = self.artifact_key(name, ARTIFACT_MANIFEST);
+        let response = self.bucket.get_object(&key)?;
+        match response.status_code() {
+            404 => Ok(None),
+            200..=299 => Ok(Some(serde_json::from_slice(response.as_slice())?)),
+            status => anyhow::bail!("Downloading {} failed with HTTP {}", key, status),
+        }
+    }
+
+    fn delete_artifacts(&self, name: &str) -> Result<()> {
+        let Some(files) = self.artifact_manifest(name)? else {
+            return Ok(());
+        };
+        // The manifest goes first so no instance fetches a half-deleted set
+        self.delete_blob(&self.artifact_key(name, ARTIFACT_MANIFEST))?;
+        for file in &files {
+            self.delete_blob(&self.artifact_key(name, file))?;
+        }
+        Ok(())
+    }
+}
+
+/// Every file below `dir`, as `/`-separated paths relative to it; hidden files (locks, partial
+/// downloads) are local to an instance and left out
+fn list_files(dir: &Path, relative: &str, files: &mut Vec<String>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().into_string()
+            .map_err(|name| anyhow::anyhow!("Artifact name {:?} is not UTF-8", name))?;
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = if relative.is_empty() { name } else { format!("{}/{}", relative, name) };
+        if entry.file_type()?.is_dir() {
+            list_files(&entry.path(), &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Where a file from a manifest goes under `dir`; manifests come from other instances, so paths
+/// leading out of `dir` are refused
+fn artifact_path(dir: &Path, file: &str) -> Result<PathBuf> {
+    let relative = Path::new(file);
+    if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+        anyhow::bail!("Refusing artifact path {:?}", file);
+    }
+    Ok(dir.join(relative))
+}
+
+impl ProofStore for S3Store {
+    fn get_proof(&self, id: &ProofId) -> Result<Option<ProofRecord>> {
+        let Some(mut record) = self.primary.get_proof(id)? else {
+            return Ok(None);
+        };
+        if let Some(blob) = self.get_blob(&self.key("proofs", id))? {
+            record.pv_stream = blob.pv_stream;
+            record.embed_proof = blob.embed_proof;
+            record.evm_inputs = blob.evm_inputs;
+        }
+        Ok(Some(record))
+    }
+
+    fn put_proof(&self, record: &ProofRecord) -> Result<()> {
+        // Upload the payload first so a record never points at a blob that is not there yet
+        self.put_blob(&self.key("proofs", &record.id), &ProofBlob {
+            pv_stream: record.pv_stream.clone(),
+            embed_proof: record.embed_proof.clone(),
+            evm_inputs: record.evm_inputs.clone(),
+        })?;
+        self.primary.put_proof(&ProofRecord { pv_stream: None, embed_proof: None, evm_inputs: None, ..record.clone() })
+    }
+
+    fn list_proofs(&self, query: &ProofQuery) -> Result<ProofPage> {
+        self.primary.list_proofs(query)
+    }
+
+    fn delete_proof(&self, id: &ProofId) -> Result<bool> {
+        // Drop the record first so nothing is left pointing at a deleted blob
+        let existed = self.primary.delete_proof(id)?;
+        self.delete_blob(&self.key("proofs", id))?;
+        self.delete_artifacts(id.as_str())?;
+        Ok(existed)
+    }
+
+    fn get_batch(&self, id: &ProofId) -> Result<Option<BatchProofRecord>> {
+        let Some(mut record) = self.primary.get_batch(id)? else {
+            return Ok(None);
+        };
+        if let Some(blob) = self.get_blob(&self.key("batches", id))? {
+            record.pv_stream = blob.pv_stream;
+            record.embed_proof = blob.embed_proof;
+            record.evm_inputs = blob.evm_inputs;
+        }
+        Ok(Some(record))
+    }
+
+    fn put_batch(&self, record: &BatchProofRecord) -> Result<()> {
+        self.put_blob(&self.key("batches", &record.id), &ProofBlob {
+            pv_stream: record.pv_stream.clone(),
+            embed_proof: record.embed_proof.clone(),
+            evm_inputs: record.evm_inputs.clone(),
+        })?;
+        self.primary.put_batch(&BatchProofRecord { pv_stream: None, embed_proof: None, evm_inputs: None, ..record.clone() })
+    }
+
+    fn get_job(&self, id: &ProofId) -> Result<Option<Job>> {
+        self.primary.get_job(id)
+    }
+
+    fn put_job(&self, job: &Job) -> Result<()> {
+        self.primary.put_job(job)
+    }
+
+    fn put_artifacts(&self, work_dir: &Path, name: &str) -> Result<()> {
+        let dir = work_dir.join(name);
+        let mut files = Vec::new();
+        list_files(&dir, "", &mut files)?;
+
+        // Streamed rather than read into memory, since proving keys run to gigabytes
+        for file in &files {
+            let key = self.artifact_key(name, file);
+            let mut reader = File::open(dir.join(file))?;
+            let status = self.bucket.put_object_stream(&mut reader, &key)?;
+            if !(200..300).contains(&status) {
+                anyhow::bail!("Uploading {} failed with HTTP {}", key, status);
+            }
+        }
+        let key = self.artifact_key(name, ARTIFACT_MANIFEST);
+        let response = self.bucket.put_object_with_content_type(&key, &serde_json::to_vec(&files)?, "application/json")?;
+        if !(200..300).contains(&response.status_code()) {
+            anyhow::bail!("Uploading {} failed with HTTP {}", key, response.status_code());
+        }
+        Ok(())
+    }
+
+    fn get_artifacts(&self, work_dir: &Path, name: &str) -> Result<bool> {
+        let Some(files) = self.artifact_manifest(name)? else {
+            return Ok(false);
+        };
+        let dir = work_dir.join(name);
+        for file in &files {
+            let path = artifact_path(&dir, file)?;
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            // Written aside and moved into place, so a failed download never looks complete
+            let partial = path.with_file_name(format!(".{}.partial", uuid::Uuid::new_v4()));
+            let key = self.artifact_key(name, file);
+            let status = self.bucket.get_object_to_writer(&key, &mut File::create(&partial)?)?;
+            if !(200..300).contains(&status) {
+                let _ = std::fs::remove_file(&partial);
+                anyhow::bail!("Downloading {} failed with HTTP {}", key, status);
+            }
+            std::fs::rename(&partial, &path)
+                .with_context(|| format!("Failed to move artifact {:?} into place", path))?;
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::Result;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+use super::{Job, ProofPage, ProofQuery, ProofStore};
+use crate::types::{BatchProofRecord, ProofId, ProofRecord, ProofSummary};
+
+/// Schema changes in order; `PRAGMA user_version` records how many have been applied
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE IF NOT EXISTS proofs (
+    id TEXT PRIMARY KEY,
+    certificate_id TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    record TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS proofs_certificate_id ON proofs (certificate_id);
+CREATE INDEX IF NOT EXISTS proofs_created_at ON proofs (created_at);
+
+CREATE TABLE IF NOT EXISTS batches (
+    id TEXT PRIMARY KEY,
+    mode TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    record TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS batch_certificates (
+    batch_id TEXT NOT NULL REFERENCES batches (id) ON DELETE CASCADE,
+    certificate_id TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (batch_id, certificate_id)
+);
+CREATE INDEX IF NOT EXISTS batch_certificates_certificate_id ON batch_certificates (certificate_id);
+
+CREATE TABLE IF NOT EXISTS jobs (
+    id TEXT PRIMARY KEY,
+    state TEXT NOT NULL,
+    error TEXT,
+    updated_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS jobs_state ON jobs (state);
+",
+    // Columns for searching proofs by batch and manufacturer
+    "
+ALTER TABLE proofs ADD COLUMN batch_number TEXT NOT NULL DEFAULT '';
+ALTER TABLE proofs ADD COLUMN manufacturer TEXT NOT NULL DEFAULT '';
+UPDATE proofs SET
+    batch_number = json_extract(record, '$.certification.batch_number'),
+    manufacturer = json_extract(record, '$.certification.material.manufacturer');
+CREATE INDEX IF NOT EXISTS proofs_batch_number ON proofs (batch_number);
+CREATE INDEX IF NOT EXISTS proofs_manufacturer ON proofs (manufacturer);
+",
+];
+
+/// Records, job state and lookup indexes in one embedded database file
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut conn = Connection::open(path)?;
+        // WAL lets readers carry on while a proof is being written
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+        Ok(SqliteStore { conn: Mutex::new(conn) })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        // Every write is a single statement or transaction, so a poisoned lock guards nothing half-done
+        self.conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn get_record<T: serde::de::DeserializeOwned>(&self, sql: &str, id: &ProofId) -> Result<Option<T>> {
+        let record: Option<String> = self.conn()
+            .query_row(sql, [id.as_str()], |row| row.get(0))
+            .optional()?;
+        record.map(|json| Ok(serde_json::from_str(&json)?)).transpose()
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let applied: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (version, sql) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", version + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Enum columns hold the same lowercase labels serde uses
+fn from_label<T: serde::de::DeserializeOwned>(label: String) -> Result<T> {
+    Ok(serde_json::from_value(serde_json::Value::String(label))?)
+}
+
+impl ProofStore for SqliteStore {
+    fn get_proof(&self, id: &ProofId) -> Result<Option<ProofRecord>> {
+        self.get_record("SELECT record FROM proofs WHERE id = ?1", id)
+    }
+
+    fn put_proof(&self, record: &ProofRecord) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO proofs (id, certificate_id, batch_number, manufacturer, mode, created_at, record)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                record.id.as_str(),
+                record.certification.certificate_id,
+                record.certification.batch_number,
+                record.certification.material.manufacturer,
+                record.mode.label(),
+                record.timestamp,
+                serde_json::to_string(record)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn list_proofs(&self, query: &ProofQuery) -> Result<ProofPage> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        for (column, filter) in [
+            ("certificate_id = ?", &query.certificate_id),
+            ("batch_number = ?", &query.batch_number),
+            ("manufacturer = ?", &query.manufacturer),
+        ] {
+            if let Some(value) = filter {
+                conditions.push(column);
+                values.push(value.clone());
+            }
+        }
+        // Timestamps are stored as UTC RFC 3339, which compares correctly as text
+        if let Some(from) = query.from {
+            conditions.push("created_at >= ?");
+            values.push(from.to_rfc3339());
+        }
+        if let Some(to) = query.to {
+            conditions.push("created_at <= ?");
+            values.push(to.to_rfc3339());
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+
+        let conn = self.conn();
+        let total: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM proofs{}", filter), params_from_iter(&values), |row| row.get(0))?;
+
+        let mut statement = conn.prepare(&format!(
+            "SELECT id, created_at, mode, certificate_id, batch_number, manufacturer FROM proofs{}
+             ORDER BY created_at DESC, id LIMIT {} OFFSET {}",
+            filter, query.page_size(), query.offset,
+        ))?;
+        let rows = statement.query_map(params_from_iter(&values), |row| {
+            Ok((row.get::<_, String>(0)?, row.get(1)?, row.get::<_, String>(2)?, row.get(3)?, row.get(4)?, row.get(5)?))
+        })?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            let (id, timestamp, mode, certificate_id, batch_number, manufacturer) = row?;
+            items.push(ProofSummary {
+                id: id.parse()?,
+                timestamp,
+                mode: from_label(mode)?,
+                certificate_id,
+                batch_number,
+                manufacturer,
+            });
+        }
+
+        Ok(ProofPage { items, total: total as usize, limit: query.page_size(), offset: query.offset })
+    }
+
+    fn delete_proof(&self, id: &ProofId) -> Result<bool> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let deleted = tx.execute("DELETE FROM proofs WHERE id = ?1", [id.as_str()])?;
+        tx.execute("DELETE FROM jobs WHERE id = ?1", [id.as_str()])?;
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    fn get_batch(&self, id: &ProofId) -> Result<Option<BatchProofRecord>> {
+        self.get_record("SELECT record FROM batches WHERE id = ?1", id)
+    }
+
+    fn put_batch(&self, record: &BatchProofRecord) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO batches (id, mode, created_at, record) VALUES (?1, ?2, ?3, ?4)",
+            params![record.id.as_str(), record.mode.label(), record.timestamp, serde_json::to_string(record)?],
+        )?;
+        tx.execute("DELETE FROM batch_certificates WHERE batch_id = ?1", [record.id.as_str()])?;
+        for entry in &record.entries {
+            tx.execute(
+                "INSERT INTO batch_certificates (batch_id, certificate_id, position) VALUES (?1, ?2, ?3)",
+                params![record.id.as_str(), entry.certificate_id, entry.index as i64],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_job(&self, id: &ProofId) -> Result<Option<Job>> {
+        let row = self.conn()
+            .query_row(
+                "SELECT state, error, updated_at FROM jobs WHERE id = ?1",
+                [id.as_str()],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, String>(2)?)),
+            )
+            .optional()?;
+
+        row.map(|(state, error, updated_at)| Ok(Job { id: id.clone(), state: from_label(state)?, updated_at, error }))
+        .transpose()
+    }
+
+    fn put_job(&self, job: &Job) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO jobs (id, state, error, updated_at) VALUES (?1, ?2, ?3, ?4)",
+            params![job.id.as_str(), job.state.label(), job.error, job.updated_at],
+        )?;
+        Ok(())
+    }
+}
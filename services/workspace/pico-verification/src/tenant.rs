@@ -0,0 +1,112 @@
+//! Organisations served by the API, each with its own proofs, jobs and quota.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use crate::error::ApiError;
+use crate::store::{ProofStore, StoreConfig};
+use crate::types::OrgId;
+
+/// Limits on what one organisation may prove; unset limits do not apply
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quota {
+    /// Certifications proven per rolling 24 hours, each certification of a batch counting once
+    #[serde(default)]
+    pub proofs_per_day: Option<usize>,
+    /// Most certifications a single batch may hold
+    #[serde(default)]
+    pub max_batch_size: Option<usize>,
+}
+
+/// One organisation's share of the server
+pub struct Tenant {
+    pub org: OrgId,
+    /// Proving artifacts of this organisation
+    pub work_dir: PathBuf,
+    pub store: Arc<dyn ProofStore>,
+    quota: Quota,
+    /// When recent proofs were started, oldest first
+    recent: Mutex<VecDeque<DateTime<Utc>>>,
+}
+
+impl Tenant {
+    /// Count `proofs` certifications against the quota, refusing them if it would be exceeded.
+    ///
+    /// Usage is counted by this process only and starts from zero after a restart.
+    pub fn reserve(&self, proofs: usize) -> Result<(), ApiError> {
+        if let Some(max) = self.quota.max_batch_size {
+            if proofs > max {
+                return Err(ApiError::QuotaExceeded(format!(
+                    "Organisation {} may prove at most {} certifications at once",
+                    self.org, max
+                )));
+            }
+        }
+
+        let Some(per_day) = self.quota.proofs_per_day else {
+            return Ok(());
+        };
+        let now = Utc::now();
+        let mut recent = self.recent.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        while recent.front().is_some_and(|started| *started <= now - Duration::days(1)) {
+            recent.pop_front();
+        }
+        if recent.len() + proofs > per_day {
+            return Err(ApiError::QuotaExceeded(format!(
+                "Organisation {} has used {} of its {} proofs for the last 24 hours",
+                self.org,
+                recent.len(),
+                per_day
+            )));
+        }
+        recent.extend(std::iter::repeat_n(now, proofs));
+        Ok(())
+    }
+
+    /// Give back `proofs` reserved certifications that were not proven after all, e.g. because
+    /// proving failed. The most recent reservations are released, which may belong to another
+    /// request; only the count matters for the quota.
+    pub fn refund(&self, proofs: usize) {
+        let mut recent = self.recent.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let kept = recent.len().saturating_sub(proofs);
+        recent.truncate(kept);
+    }
+}
+
+/// Every organisation's tenant, opened the first time it is needed
+pub struct Tenants {
+    output_dir: PathBuf,
+    store: StoreConfig,
+    quotas: HashMap<OrgId, Quota>,
+    open: Mutex<HashMap<OrgId, Arc<Tenant>>>,
+}
+
+impl Tenants {
+    pub fn new(output_dir: PathBuf, store: StoreConfig, quotas: HashMap<OrgId, Quota>) -> Self {
+        Tenants { output_dir, store, quotas, open: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn get(&self, org: &OrgId) -> Result<Arc<Tenant>> {
+        let mut open = self.open.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(tenant) = open.get(org) {
+            return Ok(Arc::clone(tenant));
+        }
+
+        let work_dir = org.dir(&self.output_dir);
+        let store = self.store.for_org(org).open(&work_dir)
+            .with_context(|| format!("Failed to open the proof store of organisation {}", org))?;
+        let tenant = Arc::new(Tenant {
+            org: org.clone(),
+            work_dir,
+            store,
+            quota: self.quotas.get(org).cloned().unwrap_or_default(),
+            recent: Mutex::new(VecDeque::new()),
+        });
+        open.insert(org.clone(), Arc::clone(&tenant));
+        Ok(tenant)
+    }
+}
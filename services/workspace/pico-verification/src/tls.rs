@@ -0,0 +1,137 @@
+//! HTTPS for the API server, picking up renewed certificates without a restart.
+
+use anyhow::{Context, Result};
+use futures_util::Stream;
+use rustls::crypto::CryptoProvider;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::ReceiverStream;
+use crate::config::TlsConfig;
+
+/// How often the certificate and key files are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Clients that have not finished the handshake by then are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Hands out whichever certificate was loaded last
+#[derive(Debug)]
+struct ReloadingCert {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(Arc::clone(&self.current.read().unwrap_or_else(|poisoned| poisoned.into_inner())))
+    }
+}
+
+/// Accepts TLS connections with the configured certificate, reloading it when its files change
+pub struct TlsListener {
+    acceptor: TlsAcceptor,
+}
+
+impl TlsListener {
+    /// Load the certificate and start watching it; must be called within the runtime
+    pub fn new(tls: &TlsConfig) -> Result<Self> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let cert = Arc::new(ReloadingCert { current: RwLock::new(Arc::new(load_certified_key(tls, &provider)?)) });
+
+        let mut config = rustls::ServerConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .context("Failed to set up TLS")?
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::clone(&cert) as Arc<dyn ResolvesServerCert>);
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        tokio::spawn(watch(tls.clone(), provider, cert));
+        Ok(TlsListener { acceptor: TlsAcceptor::from(Arc::new(config)) })
+    }
+
+    /// Connections that completed the handshake; failed handshakes are logged and dropped
+    pub fn incoming(self, listener: TcpListener) -> impl Stream<Item = std::io::Result<TlsStream<TcpStream>>> {
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            while !tx.is_closed() {
+                let (socket, peer) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(err) => {
+                        // Usually running out of file descriptors; give connections time to close
+                        tracing::warn!("Failed to accept a connection: {}", err);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let acceptor = self.acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(socket)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send(Ok(stream)).await;
+                        }
+                        Ok(Err(err)) => tracing::debug!("TLS handshake with {} failed: {}", peer, err),
+                        Err(_) => tracing::debug!("TLS handshake with {} timed out", peer),
+                    }
+                });
+            }
+        });
+        ReceiverStream::new(rx)
+    }
+}
+
+fn load_certified_key(tls: &TlsConfig, provider: &CryptoProvider) -> Result<CertifiedKey> {
+    let file = File::open(&tls.cert).with_context(|| format!("Failed to open TLS certificate {:?}", tls.cert))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to read TLS certificate {:?}", tls.cert))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificate found in {:?}", tls.cert);
+    }
+
+    let file = File::open(&tls.key).with_context(|| format!("Failed to open TLS key {:?}", tls.key))?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to read TLS key {:?}", tls.key))?
+        .with_context(|| format!("No private key found in {:?}", tls.key))?;
+    let key = provider.key_provider.load_private_key(key)
+        .with_context(|| format!("Unsupported TLS key {:?}", tls.key))?;
+
+    let certified = CertifiedKey::new(certs, key);
+    certified.keys_match().context("TLS certificate and key do not belong together")?;
+    Ok(certified)
+}
+
+fn modified(tls: &TlsConfig) -> Option<(SystemTime, SystemTime)> {
+    let cert = std::fs::metadata(&tls.cert).and_then(|meta| meta.modified()).ok()?;
+    let key = std::fs::metadata(&tls.key).and_then(|meta| meta.modified()).ok()?;
+    Some((cert, key))
+}
+
+/// Swap in the certificate whenever its files change, keeping the old one if the new one is unusable
+async fn watch(tls: TlsConfig, provider: Arc<CryptoProvider>, cert: Arc<ReloadingCert>) {
+    let mut loaded = modified(&tls);
+    let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+    loop {
+        interval.tick().await;
+        let current = modified(&tls);
+        if current.is_none() || current == loaded {
+            continue;
+        }
+        // Remember the attempt either way so a broken file is reported once, not every tick
+        loaded = current;
+        match load_certified_key(&tls, &provider) {
+            Ok(key) => {
+                *cert.current.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(key);
+                tracing::info!("Reloaded TLS certificate {:?}", tls.cert);
+            }
+            Err(err) => tracing::error!("Keeping the previous TLS certificate: {:#}", err),
+        }
+    }
+}
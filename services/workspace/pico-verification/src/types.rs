@@ -1,11 +1,17 @@
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::registry::GuestVersion;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A mill test certificate: what was made, which properties were tested and who tested them
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MaterialCertification {
+    /// Must not be blank
     pub certificate_id: String,
     pub batch_number: String,
     pub material: MaterialInfo,
@@ -15,7 +21,27 @@ pub struct MaterialCertification {
     pub verified_by: VerificationInfo,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Stands in for a private value that was removed
+pub const REDACTED: &str = "**REDACTED**";
+
+impl MaterialCertification {
+    /// A copy safe to hand out: private property values and thresholds are removed. Values the
+    /// guest checks are replaced with [`REDACTED`] rather than dropped, so their verdicts can still
+    /// be read from the proof, and the stated `passed` flags stay as they are.
+    pub fn redacted(&self) -> Self {
+        let mut cert = self.clone();
+        for prop in cert.properties_tested.private_properties.values_mut() {
+            prop.value = match prop.value {
+                serde_json::Value::Number(_) => serde_json::Value::from(REDACTED),
+                _ => serde_json::Value::Null,
+            };
+            prop.threshold = None;
+        }
+        cert
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MaterialInfo {
     pub r#type: String,
     pub designation: String,
@@ -25,64 +51,399 @@ pub struct MaterialInfo {
     pub manufacturer_location: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BatchInfo {
+    /// ISO-8601 date such as `2024-01-31`
     pub production_date: String,
+    /// ISO-8601 date, not before the production date
     pub expiration_date: Option<String>,
+    /// Greater than 0
     pub quantity: u32,
     pub units: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PropertiesInfo {
+    /// Properties disclosed in verification results, by name
     pub public_properties: HashMap<String, PropertyValue>,
+    /// Properties proven against their thresholds without disclosing them; names must not repeat public ones
     pub private_properties: HashMap<String, PropertyValue>,
     pub property_standards_mapping: HashMap<String, Vec<String>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PropertyValue {
+    /// Measured value; must be a number when there is a threshold
     pub value: serde_json::Value,
     pub unit: Option<String>,
+    /// Inclusive range the value must lie in
     pub threshold: Option<Threshold>,
+    /// Whether the value lies within the threshold, as stated by the laboratory
     pub passed: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Threshold {
     pub min: f64,
     pub max: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ComplianceInfo {
     pub standard: String,
     pub clause: String,
     pub result: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct VerificationInfo {
     pub test_report_number: String,
     pub laboratory: String,
+    /// ISO-8601 date, not before the production date
     pub test_date: String,
     pub certified_by: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// How much proving work to do for a certification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ProvingMode {
+    /// RISC-V shard proofs only; fast but neither succinct nor final
+    #[default]
+    Fast,
+    /// Full recursive proof down to a single embedded proof
+    Full,
+    /// Full proof wrapped in Groth16 for on-chain verification
+    Evm,
+}
+
+impl ProvingMode {
+    /// Whether proofs in this mode can be presented as final
+    pub fn is_final(self) -> bool {
+        !matches!(self, ProvingMode::Fast)
+    }
+
+    /// Short lowercase name, as accepted on the command line
+    pub fn label(self) -> &'static str {
+        match self {
+            ProvingMode::Fast => "fast",
+            ProvingMode::Full => "full",
+            ProvingMode::Evm => "evm",
+        }
+    }
+
+    /// Label recorded in `ZkpInfo.proof_type`
+    pub fn proof_type(self) -> &'static str {
+        match self {
+            ProvingMode::Fast => "Pico zkVM Fast Proof",
+            ProvingMode::Full => "Pico zkVM Recursive Proof",
+            ProvingMode::Evm => "Pico zkVM EVM Groth16 Proof",
+        }
+    }
+}
+
+/// Groth16 proof inputs written by Pico's EVM prover
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvmProofInputs {
+    pub riscv_v_key: String,
+    pub public_values: String,
+    pub proof: Vec<String>,
+}
+
+/// Identifier of a stored proof or batch: a UUID or a 64-character hex content hash.
+///
+/// Only ids that parse can be turned into storage paths, so a stored record can never
+/// be looked up outside the directory it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ProofId(String);
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid proof id {0:?}: expected a UUID or a 64-character hex content hash")]
+pub struct InvalidProofId(String);
+
+impl ProofId {
+    /// A fresh random id, used where there is no content to hash
+    pub fn new_random() -> Self {
+        ProofId(uuid::Uuid::new_v4().to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// `<dir>/<id>.json`
+    pub fn record_path(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.json", self.0))
+    }
+
+    /// `<dir>/<id>/`, where proving artifacts for this id are kept
+    pub fn artifacts_dir(&self, dir: &Path) -> PathBuf {
+        dir.join(&self.0)
+    }
+}
+
+impl FromStr for ProofId {
+    type Err = InvalidProofId;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let is_hash = value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit());
+        if is_hash {
+            return Ok(ProofId(value.to_ascii_lowercase()));
+        }
+        // Only the hyphenated form, so each id has a single spelling on disk
+        match uuid::Uuid::try_parse(value) {
+            Ok(id) if value.len() == 36 => Ok(ProofId(id.hyphenated().to_string())),
+            _ => Err(InvalidProofId(value.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for ProofId {
+    type Error = InvalidProofId;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ProofId> for String {
+    fn from(id: ProofId) -> Self {
+        id.0
+    }
+}
+
+impl fmt::Display for ProofId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Name of an organisation: lowercase letters, digits and hyphens, up to 63 characters.
+///
+/// Like proof ids, org ids end up in storage paths and object keys, hence the narrow alphabet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct OrgId(String);
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid organisation {0:?}: expected up to 63 lowercase letters, digits and hyphens")]
+pub struct InvalidOrgId(String);
+
+/// Organisation that owns everything created before there were organisations
+pub const DEFAULT_ORG: &str = "default";
+
+impl OrgId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.0 == DEFAULT_ORG
+    }
+
+    /// `<root>` for the default org, so data from before organisations stays where it was;
+    /// `<root>/orgs/<org>/` for every other org
+    pub fn dir(&self, root: &Path) -> PathBuf {
+        if self.is_default() {
+            root.to_path_buf()
+        } else {
+            root.join("orgs").join(&self.0)
+        }
+    }
+}
+
+impl Default for OrgId {
+    fn default() -> Self {
+        OrgId(DEFAULT_ORG.to_string())
+    }
+}
+
+impl FromStr for OrgId {
+    type Err = InvalidOrgId;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let valid = (1..=63).contains(&value.len())
+            && !value.starts_with('-')
+            && value.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+        if valid {
+            Ok(OrgId(value.to_string()))
+        } else {
+            Err(InvalidOrgId(value.to_string()))
+        }
+    }
+}
+
+impl TryFrom<String> for OrgId {
+    type Error = InvalidOrgId;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<OrgId> for String {
+    fn from(id: OrgId) -> Self {
+        id.0
+    }
+}
+
+impl fmt::Display for OrgId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Identifier of a stored proof and whether it was reused rather than freshly proven
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedProof {
+    pub proof_id: ProofId,
+    pub reused: bool,
+}
+
+/// Cost of generating a single proof
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofMetrics {
+    pub cycles: u64,
+    pub setup_ms: u64,
+    pub emulation_ms: u64,
+    pub proving_ms: u64,
+    pub peak_memory_bytes: Option<u64>,
+    pub proof_size_bytes: u64,
+}
+
+/// A generated proof as persisted in the output directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofRecord {
+    pub id: ProofId,
+    pub timestamp: String,
+    // Records written before proving modes existed were all fast proofs
+    #[serde(default)]
+    pub mode: ProvingMode,
+    pub certification: MaterialCertification,
+    /// Guest program the proof was generated by; missing on records from before it was kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program_hash: Option<String>,
+    pub pv_stream: Option<Vec<u8>>,
+    /// Base64 bincode of the recursive proof a full proof ends in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embed_proof: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evm_inputs: Option<EvmProofInputs>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<ProofMetrics>,
+}
+
+impl ProofRecord {
+    /// The record with the private property values of its certification removed
+    pub fn redacted(&self) -> Self {
+        ProofRecord { certification: self.certification.redacted(), ..self.clone() }
+    }
+}
+
+/// What proof listings show for each stored proof; it holds no property values, so listings
+/// need no redaction whoever asks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofSummary {
+    pub id: ProofId,
+    pub timestamp: String,
+    pub mode: ProvingMode,
+    pub certificate_id: String,
+    pub batch_number: String,
+    pub manufacturer: String,
+}
+
+impl From<&ProofRecord> for ProofSummary {
+    fn from(record: &ProofRecord) -> Self {
+        ProofSummary {
+            id: record.id.clone(),
+            timestamp: record.timestamp.clone(),
+            mode: record.mode,
+            certificate_id: record.certification.certificate_id.clone(),
+            batch_number: record.certification.batch_number.clone(),
+            manufacturer: record.certification.material.manufacturer.clone(),
+        }
+    }
+}
+
+/// Where one certificate sits in a batch proof
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEntry {
+    pub certificate_id: String,
+    pub index: usize,
+    pub salt: String,
+    pub commitment: String,
+    pub verdict: bool,
+    /// Sibling hashes from leaf to root; `None` where the node had no sibling
+    pub path: Vec<Option<String>>,
+}
+
+/// One aggregated proof covering many certifications
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchProofRecord {
+    pub id: ProofId,
+    pub timestamp: String,
+    pub mode: ProvingMode,
+    pub merkle_root: String,
+    pub entries: Vec<BatchEntry>,
+    pub certifications: Vec<MaterialCertification>,
+    /// Batch guest program the proof was generated by; missing on records from before it was kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program_hash: Option<String>,
+    pub pv_stream: Option<Vec<u8>>,
+    /// Base64 bincode of the recursive proof a full proof ends in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embed_proof: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evm_inputs: Option<EvmProofInputs>,
+}
+
+/// Result of checking a single certificate against a batch proof
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchInclusionResult {
+    pub batch_id: ProofId,
+    pub certificate_id: String,
+    pub index: usize,
+    pub overall_compliance: String,
+    pub merkle_root: String,
+    pub commitment: String,
+    pub path: Vec<Option<String>>,
+    pub included: bool,
+    pub proof_type: String,
+    pub verified: bool,
+    pub program: ProgramInfo,
+}
+
+/// Outcome of emulating the guest without proving
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightReport {
+    pub material_id: String,
+    pub mode: ProvingMode,
+    pub overall_compliance: String,
+    pub properties: HashMap<String, PropertyResult>,
+    pub cycles: u64,
+    pub emulation_ms: u64,
+    pub estimated_proving_seconds: f64,
+    pub estimated_memory_mb: u64,
+}
+
+/// What a verified proof says about a certification
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct VerificationResult {
     pub material_id: String,
     pub material_type: String,
     pub standard: String,
     pub timestamp: String,
     pub verification_id: String,
+    /// `PASS` when every proven property is within its threshold, otherwise `FAIL`
     pub overall_compliance: String,
     pub properties: HashMap<String, PropertyResult>,
     pub compliance_summary: ComplianceSummary,
     pub zkp_info: ZkpInfo,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PropertyResult {
     pub property: String,
     pub value: serde_json::Value,
@@ -91,7 +452,7 @@ pub struct PropertyResult {
     pub is_private: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ComplianceSummary {
     pub total_properties: usize,
     pub public_properties: usize,
@@ -100,7 +461,7 @@ pub struct ComplianceSummary {
     pub failing_properties: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ZkpInfo {
     pub implemented: bool,
     pub proof_type: String,
@@ -108,6 +469,27 @@ pub struct ZkpInfo {
     pub proof: String,
     pub public_signals: String,
     pub verified: bool,
+    /// Guest version the proof came from; absent for proofs not made by a Pico guest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program: Option<ProgramInfo>,
+}
+
+/// Which guest program version produced a proof
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProgramInfo {
+    pub program_hash: String,
+    pub version: String,
+    pub deprecated: bool,
+}
+
+impl From<&GuestVersion> for ProgramInfo {
+    fn from(version: &GuestVersion) -> Self {
+        ProgramInfo {
+            program_hash: version.program_hash.clone(),
+            version: version.version.clone(),
+            deprecated: version.deprecated,
+        }
+    }
 }
 
 pub fn read_certification(path: &Path) -> Result<MaterialCertification> {
@@ -116,6 +498,12 @@ pub fn read_certification(path: &Path) -> Result<MaterialCertification> {
     Ok(certification)
 }
 
+pub fn read_proof_record(path: &Path) -> Result<ProofRecord> {
+    let content = fs::read_to_string(path)?;
+    let record: ProofRecord = serde_json::from_str(&content)?;
+    Ok(record)
+}
+
 pub fn write_verification_results(results: &VerificationResult, path: &Path) -> Result<()> {
     let json = serde_json::to_string_pretty(results)?;
     fs::write(path, json)?;
@@ -0,0 +1,187 @@
+//! Checks on a certification before it is proven.
+//!
+//! Deserialising only guarantees the shape of a certification. These checks catch documents
+//! that parse but would make a proof meaningless: thresholds the guest cannot check, `passed`
+//! flags that contradict the values, impossible dates and the like.
+
+use chrono::{DateTime, NaiveDate};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+use crate::schema::SchemaValidator;
+use crate::types::{MaterialCertification, PropertyValue};
+
+/// A problem with one field of a certification
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    /// Dotted path of the field, empty when the document as a whole is malformed
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub(crate) fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        FieldError { field: field.into(), message: message.into() }
+    }
+
+    /// The same problem in a document nested under `prefix`, e.g. one certification of a batch
+    pub fn within(self, prefix: &str) -> Self {
+        let field = if self.field.is_empty() { prefix.to_string() } else { format!("{}.{}", prefix, self.field) };
+        FieldError { field, ..self }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.field, self.message)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    /// Where the certification was read from
+    pub source: String,
+    pub certificate_id: Option<String>,
+    pub valid: bool,
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationReport {
+    /// Check a certification that has already been read
+    pub fn new(source: String, cert: &MaterialCertification) -> Self {
+        let errors = validate(cert);
+        ValidationReport { source, certificate_id: Some(cert.certificate_id.clone()), valid: errors.is_empty(), errors }
+    }
+}
+
+/// Read and check a certification file, reporting problems instead of failing on them
+pub fn validate_file(path: &Path) -> std::io::Result<ValidationReport> {
+    let content = std::fs::read(path)?;
+    Ok(validate_json(path.display().to_string(), &content))
+}
+
+/// Parse and check a certification document: its shape against the published schema, then its
+/// contents. A document that is not JSON at all is reported as one error.
+pub fn validate_json(source: String, content: &[u8]) -> ValidationReport {
+    static SCHEMA: OnceLock<SchemaValidator> = OnceLock::new();
+
+    let failed = |source, certificate_id, errors| ValidationReport { source, certificate_id, valid: false, errors };
+    let document: Value = match serde_json::from_slice(content) {
+        Ok(document) => document,
+        Err(err) => return failed(source, None, vec![FieldError::new("", err.to_string())]),
+    };
+    let certificate_id = document.get("certificate_id").and_then(Value::as_str).map(str::to_string);
+    let errors = SCHEMA.get_or_init(SchemaValidator::of::<MaterialCertification>).check(&document);
+    if !errors.is_empty() {
+        return failed(source, certificate_id, errors);
+    }
+    match serde_json::from_value::<MaterialCertification>(document) {
+        Ok(cert) => ValidationReport::new(source, &cert),
+        Err(err) => failed(source, certificate_id, vec![FieldError::new("", err.to_string())]),
+    }
+}
+
+/// Certifications of a batch whose certificate id an earlier one already has. Entries of a batch
+/// are looked up by certificate id, so a repeated one could not be checked on its own.
+pub fn duplicate_certificates(certs: &[MaterialCertification]) -> Vec<FieldError> {
+    let mut first_seen = HashMap::new();
+    certs.iter().enumerate()
+        .filter_map(|(index, cert)| {
+            let first = *first_seen.entry(cert.certificate_id.as_str()).or_insert(index);
+            (first != index).then(|| FieldError::new(
+                format!("certifications[{}].certificate_id", index),
+                format!("{:?} is already used by certifications[{}]", cert.certificate_id, first),
+            ))
+        })
+        .collect()
+}
+
+/// Problems the guest would not notice but that make a proof of `cert` meaningless, in document order
+pub fn validate(cert: &MaterialCertification) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if cert.certificate_id.trim().is_empty() {
+        errors.push(FieldError::new("certificate_id", "must not be empty"));
+    }
+
+    let batch = &cert.batch;
+    let production_date = parse_date("batch.production_date", &batch.production_date, &mut errors);
+    if let Some(expiration_date) = &batch.expiration_date {
+        let expiration_date = parse_date("batch.expiration_date", expiration_date, &mut errors);
+        if let (Some(produced), Some(expires)) = (production_date, expiration_date) {
+            if expires < produced {
+                errors.push(FieldError::new("batch.expiration_date", format!("is before production_date {}", produced)));
+            }
+        }
+    }
+    if batch.quantity == 0 {
+        errors.push(FieldError::new("batch.quantity", "must be greater than 0"));
+    }
+
+    let props = &cert.properties_tested;
+    for (section, properties) in [("public_properties", &props.public_properties), ("private_properties", &props.private_properties)] {
+        let mut names: Vec<_> = properties.keys().collect();
+        names.sort();
+        for name in names {
+            let field = format!("properties_tested.{}.{}", section, name);
+            // The guest checks both maps as one list, so a name in each is ambiguous in the results
+            if section == "private_properties" && props.public_properties.contains_key(name) {
+                errors.push(FieldError::new(field.clone(), "is also listed in public_properties"));
+            }
+            check_property(&field, &properties[name], &mut errors);
+        }
+    }
+
+    let test_date = parse_date("verified_by.test_date", &cert.verified_by.test_date, &mut errors);
+    if let (Some(produced), Some(tested)) = (production_date, test_date) {
+        if tested < produced {
+            errors.push(FieldError::new("verified_by.test_date", format!("is before batch.production_date {}", produced)));
+        }
+    }
+    errors
+}
+
+fn check_property(field: &str, prop: &PropertyValue, errors: &mut Vec<FieldError>) {
+    let Some(threshold) = &prop.threshold else { return };
+
+    // The guest only sees numeric values; anything else silently drops out of the proof
+    let Some(value) = prop.value.as_f64() else {
+        errors.push(FieldError::new(
+            format!("{}.value", field),
+            format!("must be a number to be checked against a threshold, got {}", prop.value),
+        ));
+        return;
+    };
+    if threshold.min > threshold.max {
+        errors.push(FieldError::new(
+            format!("{}.threshold", field),
+            format!("min {} is greater than max {}", threshold.min, threshold.max),
+        ));
+        return;
+    }
+
+    let within = material_verification_lib::is_within_range(value, threshold.min, threshold.max);
+    if prop.passed != within {
+        let relation = if within { "within" } else { "outside" };
+        errors.push(FieldError::new(
+            format!("{}.passed", field),
+            format!("is {} but {} is {} {}..{}", prop.passed, value, relation, threshold.min, threshold.max),
+        ));
+    }
+}
+
+/// An ISO-8601 date, or a date and time with an offset, reduced to the calendar date
+fn parse_date(field: &str, value: &str, errors: &mut Vec<FieldError>) -> Option<NaiveDate> {
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+        .or_else(|| DateTime::parse_from_rfc3339(value).ok().map(|date| date.date_naive()));
+    if date.is_none() {
+        errors.push(FieldError::new(field, format!("{:?} is not an ISO-8601 date like 2024-01-31", value)));
+    }
+    date
+}
@@ -0,0 +1,117 @@
+//! API key and bearer token authentication, and what each role may do.
+
+mod common;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use material_pico_verify::auth::{Action, ApiKey, Authenticator, Claims, Role};
+use material_pico_verify::types::MaterialCertification;
+use sha2::{Digest, Sha256};
+
+const SECRET: &str = "test-secret";
+
+fn api_key(name: &str, key: &str, role: Role, manufacturer: Option<&str>) -> ApiKey {
+    ApiKey {
+        name: name.to_string(),
+        key_sha256: hex::encode(Sha256::digest(key.as_bytes())),
+        org: "acme".parse().unwrap(),
+        role,
+        manufacturer: manufacturer.map(str::to_string),
+    }
+}
+
+fn authenticator() -> Authenticator {
+    Authenticator::new(
+        vec![
+            api_key("acme-erp", "acme-key", Role::Manufacturer, Some("Acme")),
+            api_key("inspection-office", "inspector-key", Role::Inspector, None),
+        ],
+        Some(SECRET.to_string()),
+    )
+    .unwrap()
+}
+
+fn token(claims: &Claims, secret: &str) -> String {
+    let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+    let claims = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap());
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", header, claims).as_bytes());
+    format!("Bearer {}.{}.{}", header, claims, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+}
+
+fn claims(role: Role, expires_in: i64) -> Claims {
+    Claims {
+        sub: "someone@example.com".to_string(),
+        org: "acme".parse().unwrap(),
+        role,
+        manufacturer: None,
+        exp: chrono::Utc::now().timestamp() + expires_in,
+        nbf: None,
+    }
+}
+
+fn certification(manufacturer: &str) -> MaterialCertification {
+    let mut cert = common::certification("CERT-AUTH-001");
+    cert.material.manufacturer = manufacturer.to_string();
+    cert
+}
+
+#[test]
+fn api_keys_carry_their_role() {
+    let auth = authenticator();
+
+    let principal = auth.authenticate(Some("inspector-key"), None).unwrap();
+    assert_eq!(principal.subject, "inspection-office");
+    assert!(principal.role.allows(Action::Verify));
+    assert!(!principal.role.allows(Action::Prove));
+    assert!(!principal.role.allows(Action::Manage));
+
+    assert!(auth.authenticate(Some("wrong-key"), None).is_err());
+    assert!(auth.authenticate(None, None).is_err());
+}
+
+#[test]
+fn manufacturers_only_prove_their_own_certificates() {
+    let principal = authenticator().authenticate(Some("acme-key"), None).unwrap();
+
+    assert!(principal.role.allows(Action::Prove));
+    assert!(principal.may_prove(&certification("Acme")));
+    assert!(!principal.may_prove(&certification("Globex")));
+}
+
+#[test]
+fn only_admins_and_the_manufacturer_see_private_values() {
+    let auth = authenticator();
+    let manufacturer = auth.authenticate(Some("acme-key"), None).unwrap();
+    let inspector = auth.authenticate(Some("inspector-key"), None).unwrap();
+    let admin = auth.authenticate(None, Some(&token(&claims(Role::Admin, 60), SECRET))).unwrap();
+
+    assert!(manufacturer.may_see_private(&certification("Acme")));
+    assert!(!manufacturer.may_see_private(&certification("Globex")));
+    assert!(!inspector.may_see_private(&certification("Acme")));
+    assert!(admin.may_see_private(&certification("Globex")));
+}
+
+#[test]
+fn bearer_tokens_are_checked() {
+    let auth = authenticator();
+
+    let principal = auth.authenticate(None, Some(&token(&claims(Role::Lab, 60), SECRET))).unwrap();
+    assert_eq!(principal.role, Role::Lab);
+    assert_eq!(principal.org.as_str(), "acme");
+    assert!(principal.role.allows(Action::Sign));
+
+    assert!(auth.authenticate(None, Some(&token(&claims(Role::Admin, 60), "other-secret"))).is_err());
+    assert!(auth.authenticate(None, Some(&token(&claims(Role::Admin, -60), SECRET))).is_err());
+    // Manufacturer tokens have to say which manufacturer they act for
+    assert!(auth.authenticate(None, Some(&token(&claims(Role::Manufacturer, 60), SECRET))).is_err());
+}
+
+#[test]
+fn unauthenticated_servers_must_be_asked_for() {
+    assert!(Authenticator::new(Vec::new(), None).is_err());
+
+    let principal = Authenticator::disabled().authenticate(None, None).unwrap();
+    assert_eq!(principal.role, Role::Admin);
+}
@@ -0,0 +1,81 @@
+//! Bundles are only accepted from trusted issuers, and say no more about a proof than the server would.
+
+mod common;
+
+use common::temp_dir;
+use material_pico_verify::bundle::{self, Issuer, ProofBundle};
+use material_pico_verify::circuit;
+use material_pico_verify::guest;
+use material_pico_verify::types::{ProofRecord, ProvingMode};
+
+fn sample_record() -> ProofRecord {
+    let mut certification = common::certification("CERT-BUNDLE-001");
+    certification.properties_tested.private_properties = common::properties(serde_json::json!({
+        "carbon_content": { "value": 0.31, "unit": "%", "threshold": { "min": 0.0, "max": 0.29 }, "passed": false },
+        "surface_finish": { "value": "smooth", "unit": null, "threshold": null, "passed": true }
+    }));
+    let id = circuit::content_hash(&certification, guest::PROGRAM_HASH, ProvingMode::Fast).unwrap();
+    ProofRecord { id: id.parse().unwrap(), ..common::record(certification, vec![0, 1, 0]) }
+}
+
+fn sample_bundle() -> (ProofBundle, Issuer) {
+    let dir = temp_dir();
+    let issuer = Issuer::load_or_create(&dir.join("issuer.key")).unwrap();
+    let bundle = bundle::create_bundle(&sample_record(), &issuer, &dir).unwrap();
+    (bundle, issuer)
+}
+
+#[test]
+fn bundles_from_trusted_issuers_are_accepted() {
+    let (bundle, issuer) = sample_bundle();
+
+    let checked = bundle::verify_bundle(&bundle, &[issuer.public_key()], Some(guest::PROGRAM_HASH)).unwrap();
+    assert_eq!(checked.issuer, issuer.public_key());
+    assert!(checked.program_pinned);
+    assert_eq!(checked.verification.overall_compliance, "FAIL");
+    assert!(!checked.verification.zkp_info.verified, "a fast proof is not a final proof");
+
+    let checked = bundle::verify_bundle(&bundle, &[issuer.public_key()], None).unwrap();
+    assert!(!checked.program_pinned);
+}
+
+#[test]
+fn bundles_from_unknown_issuers_are_refused() {
+    let (bundle, _) = sample_bundle();
+    let (_, other) = sample_bundle();
+
+    assert!(bundle::verify_bundle(&bundle, &[], None).is_err(), "self-signed bundles prove nothing");
+    assert!(bundle::verify_bundle(&bundle, &[other.public_key()], None).is_err());
+}
+
+#[test]
+fn tampered_bundles_are_refused() {
+    let (mut bundle, issuer) = sample_bundle();
+    bundle.payload.public_values = hex::encode([1u8, 0]);
+
+    let err = bundle::verify_bundle(&bundle, &[issuer.public_key()], None).unwrap_err();
+    assert!(err.to_string().contains("signature"), "{:#}", err);
+}
+
+#[test]
+fn bundles_report_the_same_verdicts_as_the_server() {
+    let record = sample_record();
+    let (bundle, issuer) = sample_bundle();
+    let private = &bundle.payload.certification.properties_tested.private_properties;
+    assert_eq!(private["carbon_content"].value, "**REDACTED**");
+    assert!(private["surface_finish"].value.is_null());
+    assert!(!private["carbon_content"].passed);
+    assert!(private.values().all(|prop| prop.threshold.is_none()));
+    // Nor does the standard profile carry them
+    assert!(!bundle.payload.standard.thresholds.contains_key("carbon_content"));
+
+    let online = circuit::verify_proof(&record, &record.certification, &temp_dir()).unwrap();
+    let offline = bundle::verify_bundle(&bundle, &[issuer.public_key()], None).unwrap().verification;
+    assert_eq!(
+        serde_json::to_value(&offline.compliance_summary).unwrap(),
+        serde_json::to_value(&online.compliance_summary).unwrap()
+    );
+    assert_eq!(serde_json::to_value(&offline.properties).unwrap(), serde_json::to_value(&online.properties).unwrap());
+    assert!(!offline.properties["carbon_content"].compliant);
+    assert_eq!(offline.compliance_summary.failing_properties, 1);
+}
@@ -0,0 +1,59 @@
+//! Fixtures shared by the integration tests.
+
+// Every test crate includes this module but uses only some of it
+#![allow(dead_code)]
+
+use material_pico_verify::guest;
+use material_pico_verify::types::{MaterialCertification, ProofId, ProofRecord, PropertyValue, ProvingMode};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// A fresh, empty directory
+pub fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pico-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Properties by name, written as JSON
+pub fn properties(properties: serde_json::Value) -> HashMap<String, PropertyValue> {
+    serde_json::from_value(properties).unwrap()
+}
+
+/// A certification for steel from Acme with one passing public property and no private ones
+pub fn certification(certificate_id: &str) -> MaterialCertification {
+    serde_json::from_value(serde_json::json!({
+        "certificate_id": certificate_id,
+        "batch_number": "B-1",
+        "material": {
+            "type": "Steel", "designation": "ASTM A36", "grade": "A36", "shape": null,
+            "manufacturer": "Acme", "manufacturer_location": "Pittsburgh"
+        },
+        "batch": { "production_date": "2024-01-01", "expiration_date": null, "quantity": 1, "units": "t" },
+        "properties_tested": {
+            "public_properties": { "yield_strength": { "value": 250, "unit": "MPa", "threshold": { "min": 220, "max": 300 }, "passed": true } },
+            "private_properties": {},
+            "property_standards_mapping": {}
+        },
+        "compliance": [],
+        "verified_by": {
+            "test_report_number": "TR-1", "laboratory": "Lab", "test_date": "2024-01-02", "certified_by": "QA"
+        }
+    }))
+    .unwrap()
+}
+
+/// A fast proof of `certification` by the built-in guest, stored under a random id
+pub fn record(certification: MaterialCertification, pv_stream: Vec<u8>) -> ProofRecord {
+    ProofRecord {
+        id: ProofId::new_random(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        mode: ProvingMode::Fast,
+        certification,
+        program_hash: Some(guest::PROGRAM_HASH.to_string()),
+        pv_stream: Some(pv_stream),
+        embed_proof: None,
+        evm_inputs: None,
+        metrics: None,
+    }
+}
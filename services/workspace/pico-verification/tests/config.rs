@@ -0,0 +1,65 @@
+//! Loading and checking the configuration file.
+
+use material_pico_verify::config::{Config, LogFormat};
+use material_pico_verify::store::StoreBackend;
+use std::path::{Path, PathBuf};
+
+fn write_config(contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("pico-config-{}.toml", uuid::Uuid::new_v4()));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn example_configuration_is_valid() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config.example.toml");
+    let config = Config::from_file(&path).unwrap();
+
+    assert_eq!(config.server.port, 3000);
+    assert_eq!(config.store.backend, StoreBackend::Fs);
+    assert_eq!(config.workers.max_concurrent_proofs, Some(2));
+    assert_eq!(config.quotas.len(), 1);
+    config.validate().unwrap();
+}
+
+#[test]
+fn unset_settings_keep_their_defaults() {
+    let config = Config::from_file(&write_config("output_dir = \"/tmp/proofs\"\n")).unwrap();
+
+    assert_eq!(config.server.port, 3000);
+    assert!(config.server.cors.origins.is_empty());
+    assert!(config.server.tls.is_none());
+    assert!(config.server.metrics_address.is_none(), "metrics are not served without authentication unless asked");
+    assert_eq!(config.log.format, LogFormat::Text);
+    config.validate().unwrap();
+    // Serving needs some form of authentication
+    assert!(config.validate_server().is_err());
+}
+
+#[test]
+fn misspelt_settings_are_rejected() {
+    assert!(Config::from_file(&write_config("[server]\nprot = 8080\n")).is_err());
+}
+
+#[test]
+fn every_problem_is_reported() {
+    let path = write_config(
+        "[server]\nunix_socket = \"/tmp/api.sock\"\n\n[server.tls]\ncert = \"/missing/cert.pem\"\nkey = \"/missing/key.pem\"\n\n\
+         [server.cors]\norigins = [\"example.com\"]\n\n[workers]\nthreads = 0\n",
+    );
+    let err = Config::from_file(&path).unwrap().validate_server().unwrap_err().to_string();
+
+    assert!(err.contains("output_dir"), "{}", err);
+    assert!(err.contains("server.cors.origins"), "{}", err);
+    assert!(err.contains("server.tls.cert"), "{}", err);
+    assert!(err.contains("server.unix_socket"), "{}", err);
+    assert!(err.contains("workers.threads"), "{}", err);
+}
+
+#[test]
+fn secrets_are_not_printed() {
+    let path = write_config("output_dir = \"/tmp/proofs\"\n\n[auth]\njwt_secret = \"hunter2\"\n");
+    let printed = Config::from_file(&path).unwrap().to_redacted_toml().unwrap();
+
+    assert!(!printed.contains("hunter2"));
+}
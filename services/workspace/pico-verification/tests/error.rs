@@ -0,0 +1,38 @@
+//! Failures to load a stored record are reported by what went wrong, however the store wrapped them.
+
+mod common;
+
+use anyhow::Context;
+use material_pico_verify::error::ApiError;
+use material_pico_verify::store::{FsStore, ProofStore};
+use material_pico_verify::types::ProofId;
+use warp::http::StatusCode;
+
+const ID: &str = "record";
+
+#[test]
+fn load_failures_are_classified_through_context() {
+    let unreadable = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+    let err = Err::<(), _>(unreadable).context("Failed to read record").context("Loading from the primary store").unwrap_err();
+    assert!(matches!(ApiError::from_record_load(err, ID), ApiError::ProofUnreadable(id) if id == ID));
+
+    let malformed = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+    let err = Err::<(), _>(malformed).context("Failed to parse record").unwrap_err();
+    let classified = ApiError::from_record_load(err, ID);
+    assert!(matches!(classified, ApiError::ProofCorrupt(_)));
+    assert_eq!(classified.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let err = anyhow::anyhow!("connection reset").context("Loading from the primary store");
+    assert!(matches!(ApiError::from_record_load(err, ID), ApiError::Internal(_)));
+}
+
+#[test]
+fn malformed_records_in_a_store_are_corrupt() {
+    let dir = common::temp_dir();
+    let store = FsStore::new(dir.clone()).unwrap();
+    let id = ProofId::new_random();
+    std::fs::write(id.record_path(&dir), "{ not json").unwrap();
+
+    let err = store.get_proof(&id).unwrap_err();
+    assert!(matches!(ApiError::from_record_load(err, id.as_str()), ApiError::ProofCorrupt(_)));
+}
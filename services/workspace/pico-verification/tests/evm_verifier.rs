@@ -0,0 +1,259 @@
+//! On-chain verification of exported EVM proofs against an in-process EVM.
+//!
+//! `calldata_reaches_verifier_entry_point` runs offline against a hand-assembled
+//! contract, and `groth16_proofs_are_checked_against_the_setup` against a key made
+//! for a toy circuit. `exported_verifier_accepts_proof` needs `solc` on the PATH and an
+//! output directory containing an evm proof, passed as `PICO_EVM_FIXTURE=<dir>/<proof_id>`.
+
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::{Groth16, Proof, ProvingKey};
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use material_pico_verify::circuit::FinalProof;
+use material_pico_verify::evm::{self, VERIFIER_FUNCTION};
+use material_pico_verify::registry::{GuestVersion, OutputLayout};
+use material_pico_verify::types::{read_proof_record, EvmProofInputs, ProvingMode};
+use revm::db::{CacheDB, EmptyDB};
+use revm::primitives::{Address, Bytes, ExecutionResult, Output, TxKind};
+use revm::Evm;
+use rand::SeedableRng;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+/// Minimal local chain: deploy contracts and send calls without a node
+struct LocalChain {
+    evm: Evm<'static, (), CacheDB<EmptyDB>>,
+}
+
+impl LocalChain {
+    fn new() -> Self {
+        Self { evm: Evm::builder().with_db(CacheDB::new(EmptyDB::default())).build() }
+    }
+
+    fn transact(&mut self, to: TxKind, data: Vec<u8>) -> ExecutionResult {
+        let tx = self.evm.tx_mut();
+        tx.caller = Address::repeat_byte(0x11);
+        tx.transact_to = to;
+        tx.data = Bytes::from(data);
+        self.evm.transact_commit().expect("EVM transaction failed to execute")
+    }
+
+    fn deploy(&mut self, init_code: Vec<u8>) -> Address {
+        match self.transact(TxKind::Create, init_code) {
+            ExecutionResult::Success { output: Output::Create(_, Some(address)), .. } => address,
+            other => panic!("Contract deployment failed: {:?}", other),
+        }
+    }
+
+    fn call(&mut self, contract: Address, calldata: Vec<u8>) -> bool {
+        self.transact(TxKind::Call(contract), calldata).is_success()
+    }
+}
+
+fn sample_inputs() -> EvmProofInputs {
+    EvmProofInputs {
+        riscv_v_key: format!("0x{}", "ab".repeat(32)),
+        public_values: "0x0101010001".to_string(),
+        proof: (1..=8).map(|i| i.to_string()).collect(),
+    }
+}
+
+/// Contract that stops when the call selector matches and reverts otherwise
+fn selector_guard(selector: [u8; 4]) -> Vec<u8> {
+    let mut runtime = vec![0x60, 0x00, 0x35, 0x60, 0xe0, 0x1c, 0x63];
+    runtime.extend_from_slice(&selector);
+    runtime.extend_from_slice(&[0x14, 0x60, 0x14, 0x57, 0x60, 0x00, 0x60, 0x00, 0xfd, 0x5b, 0x00]);
+
+    // Init code copies the runtime into memory and returns it
+    let mut init = vec![0x60, runtime.len() as u8, 0x80, 0x60, 0x0b, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3];
+    init.extend(runtime);
+    init
+}
+
+#[test]
+fn calldata_reaches_verifier_entry_point() {
+    let mut chain = LocalChain::new();
+    let guard = chain.deploy(selector_guard(evm::function_selector(VERIFIER_FUNCTION)));
+
+    let calldata = evm::encode_calldata(&sample_inputs()).unwrap();
+    assert_eq!(calldata.len(), 4 + 10 * 32 + 32 + 32);
+    assert!(chain.call(guard, calldata.clone()));
+
+    let mut wrong_selector = calldata;
+    wrong_selector[0] ^= 0xff;
+    assert!(!chain.call(guard, wrong_selector));
+}
+
+#[test]
+fn calldata_encodes_public_values_after_static_head() {
+    let calldata = evm::encode_calldata(&sample_inputs()).unwrap();
+    let body = &calldata[4..];
+
+    assert_eq!(&body[..32], [0xab; 32].as_slice());
+    assert_eq!(&body[62..64], &[0x01, 0x40]);
+    assert_eq!(body[2 * 32 + 31], 1);
+    assert_eq!(body[9 * 32 + 31], 8);
+    assert_eq!(body[10 * 32 + 31], 5);
+    assert_eq!(&body[11 * 32..11 * 32 + 5], &[1, 1, 1, 0, 1]);
+}
+
+/// Two public inputs multiplied together, shaped like the circuit Pico wraps its proofs in
+struct ToyCircuit {
+    inputs: [Fr; 2],
+}
+
+impl ConstraintSynthesizer<Fr> for ToyCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let a = cs.new_input_variable(|| Ok(self.inputs[0]))?;
+        let b = cs.new_input_variable(|| Ok(self.inputs[1]))?;
+        let product = cs.new_witness_variable(|| Ok(self.inputs[0] * self.inputs[1]))?;
+        cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + product)
+    }
+}
+
+/// The verifying key as the constants a gnark Solidity verifier declares
+fn write_verifier(key: &ProvingKey<Bn254>, output_dir: &Path) {
+    let vk = &key.vk;
+    let mut constants = vec![
+        ("ALPHA_X".to_string(), vk.alpha_g1.x.to_string()),
+        ("ALPHA_Y".to_string(), vk.alpha_g1.y.to_string()),
+    ];
+    for (name, point) in [("BETA_NEG", -vk.beta_g2), ("GAMMA_NEG", -vk.gamma_g2), ("DELTA_NEG", -vk.delta_g2)] {
+        constants.push((format!("{}_X_0", name), point.x.c0.to_string()));
+        constants.push((format!("{}_X_1", name), point.x.c1.to_string()));
+        constants.push((format!("{}_Y_0", name), point.y.c0.to_string()));
+        constants.push((format!("{}_Y_1", name), point.y.c1.to_string()));
+    }
+    for (name, point) in ["CONSTANT", "PUB_0", "PUB_1"].into_iter().zip(&vk.gamma_abc_g1) {
+        constants.push((format!("{}_X", name), point.x.to_string()));
+        constants.push((format!("{}_Y", name), point.y.to_string()));
+    }
+
+    let body: String = constants.iter()
+        .map(|(name, value)| format!("    uint256 constant {} = {};\n", name, value))
+        .collect();
+    let evm_dir = output_dir.join(evm::EVM_DIR);
+    std::fs::create_dir_all(&evm_dir).unwrap();
+    std::fs::write(evm_dir.join("Groth16Verifier.sol"), format!("contract Verifier {{\n{}}}\n", body)).unwrap();
+}
+
+/// A proof's eight words in the order the Solidity verifier takes them
+fn proof_words(proof: &Proof<Bn254>) -> Vec<String> {
+    [proof.a.x, proof.a.y, proof.b.x.c1, proof.b.x.c0, proof.b.y.c1, proof.b.y.c0, proof.c.x, proof.c.y]
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[test]
+fn groth16_proofs_are_checked_against_the_setup() {
+    let output_dir = std::env::temp_dir().join(format!("pico-evm-setup-{}", std::process::id()));
+    let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+
+    let riscv_vkey = format!("0x{}", "0a".repeat(32));
+    let public_values = vec![1u8, 1, 0, 1];
+    let mut digest: [u8; 32] = Sha256::digest(&public_values).into();
+    digest[0] &= 0x1f;
+    let inputs = [
+        Fr::from_str(&num_bigint::BigUint::from_bytes_be(&[0x0a; 32]).to_string()).unwrap(),
+        Fr::from_str(&num_bigint::BigUint::from_bytes_be(&digest).to_string()).unwrap(),
+    ];
+
+    let key = Groth16::<Bn254>::generate_random_parameters_with_reduction(ToyCircuit { inputs }, &mut rng).unwrap();
+    let proof = Groth16::<Bn254>::create_random_proof_with_reduction(ToyCircuit { inputs }, &key, &mut rng).unwrap();
+    write_verifier(&key, &output_dir);
+    let setup = evm::setup_key(&output_dir).unwrap();
+
+    let evm_inputs = EvmProofInputs {
+        riscv_v_key: riscv_vkey.clone(),
+        public_values: format!("0x{}", hex::encode(&public_values)),
+        proof: proof_words(&proof),
+    };
+    evm::verify_groth16(&setup, &evm_inputs, &riscv_vkey).unwrap();
+
+    // Another guest's key, other public values or another setup's proof are all refused
+    assert!(evm::verify_groth16(&setup, &evm_inputs, &format!("0x{}", "0b".repeat(32))).is_err());
+    let tampered = EvmProofInputs { public_values: "0x01010101".to_string(), ..evm_inputs.clone() };
+    assert!(evm::verify_groth16(&setup, &tampered, &riscv_vkey).is_err());
+
+    // Without a published key, proofs of a guest are checked against the key the prover
+    // reported when it proved that guest with the setup
+    let version = GuestVersion {
+        program_hash: "0c".repeat(32),
+        version: "0.2.0".to_string(),
+        output_layout: OutputLayout::ComplianceV1,
+        verifying_key: None,
+        released: None,
+        deprecated: false,
+        untagged: false,
+    };
+    let proof = FinalProof::of(ProvingMode::Evm, Some(&public_values), Some(&evm_inputs));
+    assert!(proof.check(&version, || evm::setup(&output_dir, &version.program_hash)).is_err());
+    assert!(evm::record_guest_vkey(&output_dir.join(evm::EVM_DIR), &version.program_hash, &riscv_vkey).unwrap());
+    proof.check(&version, || evm::setup(&output_dir, &version.program_hash)).unwrap();
+    let published = GuestVersion { verifying_key: Some(format!("0x{}", "0b".repeat(32))), ..version };
+    assert!(proof.check(&published, || evm::setup(&output_dir, &published.program_hash)).is_err());
+    assert!(FinalProof::of(ProvingMode::Full, Some(&public_values), None).check(&published, || unreachable!()).is_err());
+    let other = Groth16::<Bn254>::generate_random_parameters_with_reduction(ToyCircuit { inputs }, &mut rng).unwrap();
+    write_verifier(&other, &output_dir);
+    assert!(evm::verify_groth16(&evm::setup_key(&output_dir).unwrap(), &evm_inputs, &riscv_vkey).is_err());
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
+
+/// Compile every exported contract with solc and return (name, init code) pairs
+fn compile(contracts_dir: &Path) -> Vec<(String, Vec<u8>)> {
+    let sources: Vec<_> = std::fs::read_dir(contracts_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sol"))
+        .collect();
+
+    let output = Command::new("solc")
+        .args(["--optimize", "--combined-json", "bin,hashes"])
+        .args(&sources)
+        .output()
+        .expect("solc must be installed to run this test");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let compiled: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    compiled["contracts"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .filter(|(_, contract)| contract["hashes"].get(VERIFIER_FUNCTION).is_some())
+        .map(|(name, contract)| (name.clone(), hex::decode(contract["bin"].as_str().unwrap()).unwrap()))
+        .collect()
+}
+
+#[test]
+#[ignore = "requires solc and PICO_EVM_FIXTURE pointing at an evm proof"]
+fn exported_verifier_accepts_proof() {
+    let fixture = std::env::var("PICO_EVM_FIXTURE").expect("PICO_EVM_FIXTURE is not set");
+    let fixture = Path::new(&fixture);
+    let output_dir = fixture.parent().unwrap();
+
+    let record = read_proof_record(&fixture.with_extension("json")).unwrap();
+    let export = evm::export_verifier(output_dir, &record).unwrap();
+    let export_dir = evm::write_export(&export, &std::env::temp_dir()).unwrap();
+
+    let (name, init_code) = compile(&export_dir).into_iter().next().expect("no contract exposes verifyPicoProof");
+    let mut chain = LocalChain::new();
+    let verifier = chain.deploy(init_code);
+
+    let calldata = hex::decode(export.calldata.trim_start_matches("0x")).unwrap();
+    assert!(chain.call(verifier, calldata), "{} rejected a valid proof", name);
+
+    // Checking the proof off-chain reads the same key out of the setup
+    let inputs = record.evm_inputs.as_ref().unwrap();
+    evm::verify_groth16(&evm::setup_key(output_dir).unwrap(), inputs, &inputs.riscv_v_key).unwrap();
+
+    // Flipping a committed compliance flag must invalidate the proof
+    let mut tampered = record.evm_inputs.unwrap();
+    let mut public_values = hex::decode(tampered.public_values.trim_start_matches("0x")).unwrap();
+    public_values[0] ^= 1;
+    tampered.public_values = hex::encode(public_values);
+    assert!(!chain.call(verifier, evm::encode_calldata(&tampered).unwrap()));
+}
@@ -0,0 +1,81 @@
+//! Proofs are tagged with the guest program that produced them and only accepted from known versions.
+
+mod common;
+
+use material_pico_verify::circuit;
+use material_pico_verify::guest;
+use material_pico_verify::registry::{self, GuestVersion, OutputLayout};
+use material_pico_verify::types::{ProofRecord, ProvingMode};
+
+fn sample_record(program_hash: Option<&str>) -> ProofRecord {
+    ProofRecord {
+        mode: ProvingMode::Full,
+        program_hash: program_hash.map(str::to_string),
+        ..common::record(common::certification("CERT-GUEST-001"), vec![1, 1])
+    }
+}
+
+#[test]
+fn program_hash_matches_the_embedded_guest() {
+    assert_eq!(guest::PROGRAM_HASH, circuit::program_hash(guest::ELF));
+    assert_eq!(guest::BATCH_PROGRAM_HASH, circuit::program_hash(guest::BATCH_ELF));
+    assert_eq!(circuit::guest_program_hash(), guest::PROGRAM_HASH);
+}
+
+/// An earlier release, as an operator would list it in the configuration
+const OLD_RELEASE: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+/// An earlier batch guest, whose proofs cannot be read as single-certificate proofs
+const OLD_BATCH_RELEASE: &str = "0000000000000000000000000000000000000000000000000000000000000002";
+
+fn register_old_releases() {
+    let version = |program_hash: &str, output_layout| GuestVersion {
+        program_hash: program_hash.to_string(),
+        version: "0.0.9".to_string(),
+        output_layout,
+        verifying_key: None,
+        released: Some("2024-06-01".parse().unwrap()),
+        deprecated: true,
+        untagged: false,
+    };
+    registry::use_guest_versions(vec![
+        version(OLD_RELEASE, OutputLayout::ComplianceV1),
+        version(OLD_BATCH_RELEASE, OutputLayout::BatchV1),
+    ]);
+}
+
+#[test]
+fn proofs_of_known_versions_are_verified() {
+    register_old_releases();
+
+    let record = sample_record(Some(guest::PROGRAM_HASH));
+    let result = circuit::verify_proof(&record, &record.certification, &std::env::temp_dir()).unwrap();
+    assert_eq!(result.overall_compliance, "PASS");
+    assert!(!result.zkp_info.verified, "the mode alone does not make a proof final");
+    let program = result.zkp_info.program.unwrap();
+    assert_eq!(program.version, guest::VERSION);
+    assert!(!program.deprecated);
+
+    // Earlier releases stay verifiable but are reported as deprecated
+    let record = sample_record(Some(OLD_RELEASE));
+    let program = circuit::verify_proof(&record, &record.certification, &std::env::temp_dir()).unwrap().zkp_info.program.unwrap();
+    assert_eq!(program.version, "0.0.9");
+    assert!(program.deprecated);
+}
+
+#[test]
+fn proofs_of_unknown_programs_are_rejected() {
+    register_old_releases();
+
+    let record = sample_record(Some(&"f".repeat(64)));
+    let err = circuit::verify_proof(&record, &record.certification, &std::env::temp_dir()).unwrap_err().to_string();
+    assert!(err.contains("not a known version"), "{}", err);
+
+    // A batch guest's output cannot be decoded as a single certificate's
+    let record = sample_record(Some(OLD_BATCH_RELEASE));
+    assert!(circuit::verify_proof(&record, &record.certification, &std::env::temp_dir()).is_err());
+
+    // Records from before proofs were tagged cannot be attributed to any guest unless the
+    // configuration names the release they came from
+    let record = sample_record(None);
+    assert!(circuit::verify_proof(&record, &record.certification, &std::env::temp_dir()).is_err());
+}
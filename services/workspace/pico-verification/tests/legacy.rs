@@ -0,0 +1,129 @@
+//! Legacy snarkjs proofs are checked with the Groth16 pairing equation.
+//!
+//! The legacy service never kept its verification key, so the public signals in
+//! `services/legacy/outputs` are proven again with a key made for a circuit that takes them
+//! as its public inputs, written out the way snarkjs writes its files.
+
+mod common;
+
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_groth16::{Groth16, Proof, ProvingKey};
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable};
+use material_pico_verify::legacy::{self, LegacyProofBundle};
+use rand::SeedableRng;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+fn legacy_outputs() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../legacy/outputs")
+}
+
+/// Public signals bound to the proof by copying each into a witness
+struct SignalsCircuit {
+    signals: Vec<Fr>,
+}
+
+impl ConstraintSynthesizer<Fr> for SignalsCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        for signal in self.signals {
+            let input = cs.new_input_variable(|| Ok(signal))?;
+            let copy = cs.new_witness_variable(|| Ok(signal))?;
+            cs.enforce_constraint(lc!() + input, lc!() + Variable::One, lc!() + copy)?;
+        }
+        Ok(())
+    }
+}
+
+fn g1(point: &G1Affine) -> serde_json::Value {
+    json!([point.x.to_string(), point.y.to_string(), "1"])
+}
+
+fn fq2(value: &Fq2) -> serde_json::Value {
+    json!([value.c0.to_string(), value.c1.to_string()])
+}
+
+fn g2(point: &G2Affine) -> serde_json::Value {
+    json!([fq2(&point.x), fq2(&point.y), ["1", "0"]])
+}
+
+/// Write `proof.json` and `verification_key.json` into `dir` as snarkjs would
+fn write_snarkjs_files(dir: &Path, key: &ProvingKey<Bn254>, proof: &Proof<Bn254>, signals: usize) {
+    let vk = &key.vk;
+    let vkey = json!({
+        "protocol": "groth16",
+        "curve": "bn128",
+        "nPublic": signals,
+        "vk_alpha_1": g1(&vk.alpha_g1),
+        "vk_beta_2": g2(&vk.beta_g2),
+        "vk_gamma_2": g2(&vk.gamma_g2),
+        "vk_delta_2": g2(&vk.delta_g2),
+        "IC": vk.gamma_abc_g1.iter().map(g1).collect::<Vec<_>>(),
+    });
+    let proof = json!({
+        "pi_a": g1(&proof.a),
+        "pi_b": g2(&proof.b),
+        "pi_c": g1(&proof.c),
+        "protocol": "groth16",
+        "curve": "bn128",
+    });
+    std::fs::write(dir.join("verification_key.json"), vkey.to_string()).unwrap();
+    std::fs::write(dir.join("proof.json"), proof.to_string()).unwrap();
+}
+
+/// A bundle read from the snarkjs files, proving the legacy service's public signals
+fn legacy_bundle() -> LegacyProofBundle {
+    let outputs = legacy_outputs();
+    let signals: Vec<String> = serde_json::from_str(&std::fs::read_to_string(outputs.join("public.json")).unwrap()).unwrap();
+    let signals: Vec<Fr> = signals.iter().map(|signal| Fr::from_str(signal).unwrap()).collect();
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+    let count = signals.len();
+    let key = Groth16::<Bn254>::generate_random_parameters_with_reduction(SignalsCircuit { signals: signals.clone() }, &mut rng).unwrap();
+    let proof = Groth16::<Bn254>::create_random_proof_with_reduction(SignalsCircuit { signals }, &key, &mut rng).unwrap();
+
+    let dir = common::temp_dir();
+    write_snarkjs_files(&dir, &key, &proof, count);
+    legacy::read_legacy_bundle(
+        &dir.join("proof.json"),
+        &outputs.join("public.json"),
+        &dir.join("verification_key.json"),
+        Some(&outputs.join("verification.json")),
+    )
+    .unwrap()
+}
+
+#[test]
+fn legacy_outputs_verify() {
+    let result = legacy::verify_legacy_proof(&legacy_bundle()).unwrap();
+    assert!(result.zkp_info.verified);
+    assert_eq!(result.overall_compliance, "PASS");
+    assert_eq!(result.material_id, "ALU-12345");
+
+    // Properties are named from the report and their thresholds read back from the signals
+    let tensile = &result.properties["tensile_strength"];
+    assert!(tensile.compliant);
+    assert_eq!(tensile.thresholds, json!({ "min": 42.0, "max": 100.0 }));
+    assert_eq!(result.compliance_summary.total_properties, 5);
+}
+
+#[test]
+fn tampered_public_signals_are_rejected() {
+    let mut bundle = legacy_bundle();
+    // Claim tensile strength met a stricter minimum than the one proven
+    let proven = bundle.public_signals.iter().position(|signal| signal == "42000000").unwrap();
+    bundle.public_signals[proven] = "60000000".to_string();
+
+    let result = legacy::verify_legacy_proof(&bundle).unwrap();
+    assert!(!result.zkp_info.verified);
+    assert_eq!(result.overall_compliance, "FAIL");
+    assert!(result.properties.values().all(|property| !property.compliant));
+}
+
+#[test]
+fn points_off_the_curve_are_rejected() {
+    let mut bundle = legacy_bundle();
+    bundle.proof.pi_a[1] = (Fq::from_str(&bundle.proof.pi_a[1]).unwrap() + Fq::from(1u64)).to_string();
+    assert!(legacy::verify_legacy_proof(&bundle).is_err());
+}
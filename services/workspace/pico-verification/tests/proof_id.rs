@@ -0,0 +1,69 @@
+//! Proof ids come from URLs and file names, so only UUIDs and content hashes are accepted,
+//! each with a single spelling, and they never lead outside the directory they are joined to.
+
+use material_pico_verify::types::ProofId;
+use std::path::Path;
+
+const HASH: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+const UUID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+fn parse(value: &str) -> Option<ProofId> {
+    value.parse().ok()
+}
+
+#[test]
+fn uuids_and_content_hashes_are_accepted() {
+    assert_eq!(parse(HASH).unwrap().as_str(), HASH);
+    assert_eq!(parse(UUID).unwrap().as_str(), UUID);
+    let random = ProofId::new_random();
+    assert_eq!(parse(random.as_str()), Some(random));
+}
+
+#[test]
+fn uppercase_ids_are_stored_in_lowercase() {
+    assert_eq!(parse(&HASH.to_ascii_uppercase()).unwrap().as_str(), HASH);
+    assert_eq!(parse(&UUID.to_ascii_uppercase()).unwrap().as_str(), UUID);
+}
+
+#[test]
+fn other_spellings_are_rejected() {
+    // Content hashes are exactly 64 hex digits
+    assert!(parse(&HASH[..63]).is_none());
+    assert!(parse(&format!("{}0", HASH)).is_none());
+    assert!(parse(&HASH.replace('a', "g")).is_none());
+
+    // UUIDs only in their hyphenated form
+    assert!(parse(&UUID.replace('-', "")).is_none());
+    assert!(parse(&format!("{{{}}}", UUID)).is_none());
+    assert!(parse(&format!("urn:uuid:{}", UUID)).is_none());
+
+    assert!(parse("").is_none());
+}
+
+#[test]
+fn path_components_are_rejected() {
+    for value in [
+        "..",
+        "../etc/passwd",
+        &format!("../{}", &HASH[3..]),
+        &format!("{}/..", &UUID[..33]),
+        &format!("/{}", &HASH[1..]),
+        &format!("{}.json", &HASH[..59]),
+        &format!("{}\0", &HASH[..63]),
+    ] {
+        assert!(parse(value).is_none(), "{:?} was accepted", value);
+    }
+
+    // Ids that did parse stay one level below the directory
+    let id = parse(HASH).unwrap();
+    let dir = Path::new("/srv/proofs");
+    assert_eq!(id.artifacts_dir(dir).parent(), Some(dir));
+    assert_eq!(id.record_path(dir), dir.join(format!("{}.json", HASH)));
+}
+
+#[test]
+fn ids_deserialize_only_when_valid() {
+    let id: ProofId = serde_json::from_str(&format!("\"{}\"", UUID.to_ascii_uppercase())).unwrap();
+    assert_eq!(id.as_str(), UUID);
+    assert!(serde_json::from_str::<ProofId>("\"../secret\"").is_err());
+}
@@ -0,0 +1,157 @@
+//! The S3 proof store against an in-process stand-in for MinIO.
+//!
+//! The stand-in implements just enough of the S3 object API (path-style PUT, GET and
+//! DELETE on keys, and starting a multipart upload, which the store's streamed uploads
+//! abandon for small files) to exercise the store; it does not check request signatures.
+
+mod common;
+
+use common::temp_dir;
+use material_pico_verify::store::{FsStore, ProofStore, S3Config, S3Store};
+use material_pico_verify::types::{ProofId, ProofRecord};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use warp::http::{Method, Response, StatusCode};
+use warp::hyper::body::Bytes;
+use warp::Filter;
+
+const BUCKET: &str = "proofs";
+
+type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+/// Serve an in-memory bucket on an ephemeral port
+fn start_object_store() -> (SocketAddr, Objects) {
+    let objects = Objects::default();
+    let state = Arc::clone(&objects);
+
+    let routes = warp::method()
+        .and(warp::path::full())
+        .and(warp::body::bytes())
+        .map(move |method: Method, path: warp::path::FullPath, body: Bytes| {
+            let key = path.as_str().trim_start_matches('/').to_string();
+            let mut objects = state.lock().unwrap();
+            let response = Response::builder();
+            match method {
+                Method::PUT => {
+                    objects.insert(key, body.to_vec());
+                    response.header("ETag", "\"stand-in\"").body(Vec::new())
+                },
+                Method::GET => match objects.get(&key) {
+                    Some(object) => response.body(object.clone()),
+                    None => response
+                        .status(StatusCode::NOT_FOUND)
+                        .body(b"<Error><Code>NoSuchKey</Code></Error>".to_vec()),
+                },
+                Method::DELETE => {
+                    objects.remove(&key);
+                    response.status(StatusCode::NO_CONTENT).body(Vec::new())
+                },
+                Method::POST => {
+                    let (bucket, key) = key.split_once('/').unwrap_or((&key, ""));
+                    let result = format!(
+                        "<InitiateMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><UploadId>stand-in</UploadId></InitiateMultipartUploadResult>",
+                        bucket, key
+                    );
+                    response.body(result.into_bytes())
+                },
+                _ => response.status(StatusCode::METHOD_NOT_ALLOWED).body(Vec::new()),
+            }
+            .unwrap()
+        });
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let (address, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+            sender.send(address).unwrap();
+            server.await;
+        });
+    });
+
+    (receiver.recv().unwrap(), objects)
+}
+
+fn open_stores(address: SocketAddr, dir: &Path) -> (Arc<FsStore>, S3Store) {
+    let primary = Arc::new(FsStore::new(dir.to_path_buf()).unwrap());
+    let config = S3Config {
+        endpoint: format!("http://{}", address),
+        region: "us-east-1".to_string(),
+        bucket: BUCKET.to_string(),
+        prefix: "tenant-a/".to_string(),
+        access_key: Some("minioadmin".to_string()),
+        secret_key: Some("minioadmin".to_string()),
+    };
+    let store = S3Store::new(&config, primary.clone()).unwrap();
+    (primary, store)
+}
+
+fn sample_record() -> ProofRecord {
+    common::record(common::certification("CERT-S3-001"), vec![1, 1, 0, 1])
+}
+
+#[test]
+fn proof_payload_is_kept_in_object_storage() {
+    let (address, objects) = start_object_store();
+    let dir = temp_dir();
+    let (primary, store) = open_stores(address, &dir);
+
+    let record = sample_record();
+    store.put_proof(&record).unwrap();
+
+    // The payload sits under the configured prefix, the record in the primary store
+    let key = format!("{}/tenant-a/proofs/{}.json", BUCKET, record.id);
+    assert!(objects.lock().unwrap().contains_key(&key), "missing object {}", key);
+
+    let metadata = primary.get_proof(&record.id).unwrap().unwrap();
+    assert_eq!(metadata.certification.certificate_id, record.certification.certificate_id);
+    assert!(metadata.pv_stream.is_none());
+
+    let loaded = store.get_proof(&record.id).unwrap().unwrap();
+    assert_eq!(loaded.pv_stream, record.pv_stream);
+}
+
+#[test]
+fn missing_proof_is_not_found() {
+    let (address, _objects) = start_object_store();
+    let dir = temp_dir();
+    let (_primary, store) = open_stores(address, &dir);
+
+    assert!(store.get_proof(&ProofId::new_random()).unwrap().is_none());
+}
+
+#[test]
+fn records_written_before_object_storage_keep_their_payload() {
+    let (address, _objects) = start_object_store();
+    let dir = temp_dir();
+    let (primary, store) = open_stores(address, &dir);
+
+    let record = sample_record();
+    primary.put_proof(&record).unwrap();
+
+    let loaded = store.get_proof(&record.id).unwrap().unwrap();
+    assert_eq!(loaded.pv_stream, record.pv_stream);
+}
+
+#[test]
+fn artifacts_are_shared_between_instances() {
+    let (address, _objects) = start_object_store();
+    let (first, second) = (temp_dir(), temp_dir());
+    let (_primary, store) = open_stores(address, &first);
+
+    assert!(!store.get_artifacts(&second, "evm").unwrap(), "nothing has been shared yet");
+
+    let evm_dir = first.join("evm");
+    std::fs::create_dir_all(evm_dir.join("vk")).unwrap();
+    std::fs::write(evm_dir.join("vm_pk"), b"proving key").unwrap();
+    std::fs::write(evm_dir.join("vk").join("groth16.json"), b"{}").unwrap();
+    std::fs::write(evm_dir.join(".lock"), b"").unwrap();
+    store.put_artifacts(&first, "evm").unwrap();
+
+    assert!(store.get_artifacts(&second, "evm").unwrap());
+    assert_eq!(std::fs::read(second.join("evm").join("vm_pk")).unwrap(), b"proving key");
+    assert_eq!(std::fs::read(second.join("evm").join("vk").join("groth16.json")).unwrap(), b"{}");
+    assert!(!second.join("evm").join(".lock").exists(), "locks stay with their instance");
+}
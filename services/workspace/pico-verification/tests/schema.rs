@@ -0,0 +1,61 @@
+//! The published JSON Schemas follow the types, and documents are checked against them field by field.
+
+use material_pico_verify::api::GenerateBatchProofRequest;
+use material_pico_verify::schema::{SchemaType, SchemaValidator};
+use material_pico_verify::validation;
+use std::path::Path;
+
+/// Run with `UPDATE_SCHEMAS=1` to write the schemas after changing the types
+#[test]
+fn published_schemas_match_the_types() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("schemas");
+    for kind in SchemaType::ALL {
+        let path = dir.join(kind.file_name());
+        let generated = serde_json::to_string_pretty(&kind.schema()).unwrap() + "\n";
+        if std::env::var_os("UPDATE_SCHEMAS").is_some() {
+            std::fs::write(&path, &generated).unwrap();
+        }
+        let published = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(published == generated, "{} is out of date; rerun with UPDATE_SCHEMAS=1", path.display());
+    }
+}
+
+#[test]
+fn sample_certifications_are_valid() {
+    let samples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../web/app");
+    for name in ["steel-rod.json", "aluminum-bar.json", "copper-sheet.json"] {
+        let report = validation::validate_file(&samples.join(name)).unwrap();
+        assert!(report.valid, "{}: {:?}", name, report.errors);
+    }
+}
+
+#[test]
+fn schema_errors_point_at_the_field() {
+    let samples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../web/app");
+    let mut cert: serde_json::Value = serde_json::from_slice(&std::fs::read(samples.join("steel-rod.json")).unwrap()).unwrap();
+    cert["batch"].as_object_mut().unwrap().remove("quantity");
+    cert["compliance"][0]["result"] = serde_json::json!(true);
+    cert["properties_tested"]["public_properties"]["tensile_strength"]["threshold"]["min"] = serde_json::json!("85");
+
+    let report = validation::validate_json("request".to_string(), &serde_json::to_vec(&cert).unwrap());
+    let mut fields: Vec<_> = report.errors.iter().map(|error| error.field.as_str()).collect();
+    fields.sort();
+    assert_eq!(fields, [
+        "batch.quantity",
+        "compliance[0].result",
+        "properties_tested.public_properties.tensile_strength.threshold.min",
+    ]);
+    assert_eq!(report.certificate_id.as_deref(), Some("CERT-SS-456789"));
+}
+
+#[test]
+fn requests_are_checked_before_they_are_read() {
+    let validator = SchemaValidator::of::<GenerateBatchProofRequest>();
+    let request = serde_json::json!({ "certifications": [{ "certificate_id": 7 }], "mode": "slow" });
+
+    let errors = validator.check(&request);
+    let error = |field: &str| errors.iter().find(|error| error.field == field).map(|error| error.message.as_str());
+    assert_eq!(error("certifications[0].certificate_id"), Some(r#"7 is not of type "string""#));
+    assert_eq!(error("certifications[0].batch"), Some("is required"));
+    assert_eq!(error("mode"), Some(r#""slow" is not one of "fast", "full", "evm""#));
+}
@@ -0,0 +1,65 @@
+//! Shipments are proven one certificate at a time, and a rerun keeps what earlier runs proved.
+
+mod common;
+
+use common::{certification, temp_dir};
+use material_pico_verify::circuit;
+use material_pico_verify::shipment::{self, CertificateStatus, ShipmentSource, ShipmentSummary};
+use material_pico_verify::store::StoreConfig;
+use material_pico_verify::types::{MaterialCertification, ProofRecord, ProvingMode};
+use std::path::PathBuf;
+
+/// A proof an earlier run stored under the id proving `cert` again would produce
+fn proven(cert: &MaterialCertification, verdict: u8) -> ProofRecord {
+    let program_hash = circuit::guest_program_hash();
+    ProofRecord {
+        id: circuit::content_hash(cert, program_hash, ProvingMode::Fast).unwrap().parse().unwrap(),
+        program_hash: Some(program_hash.to_string()),
+        ..common::record(cert.clone(), vec![verdict, verdict])
+    }
+}
+
+#[test]
+fn rerun_reuses_finished_proofs_and_reports_each_certificate() {
+    let dir = temp_dir();
+    let shipment_dir = dir.join("shipment-42");
+    std::fs::create_dir_all(&shipment_dir).unwrap();
+    let store = StoreConfig::default().open(&dir).unwrap();
+
+    let passing = certification("CERT-A");
+    let failing = certification("CERT-B");
+    let mut invalid = certification("CERT-C");
+    invalid.certificate_id.clear();
+    for (file, cert) in [("a.json", &passing), ("b.json", &failing), ("c.json", &invalid)] {
+        std::fs::write(shipment_dir.join(file), serde_json::to_vec(cert).unwrap()).unwrap();
+    }
+    std::fs::write(shipment_dir.join("d.json"), "not json").unwrap();
+    std::fs::write(shipment_dir.join("notes.txt"), "ignored").unwrap();
+    store.put_proof(&proven(&passing, 1)).unwrap();
+    store.put_proof(&proven(&failing, 0)).unwrap();
+
+    let summary_path = dir.join("summary.json");
+    let source = ShipmentSource::Dir(shipment_dir);
+    let summary = shipment::prove_shipment(store.as_ref(), &dir, &source, ProvingMode::Fast, false, 4, &summary_path).unwrap();
+
+    assert_eq!(summary.shipment, "shipment-42");
+    let statuses: Vec<_> = summary.certificates.iter().map(|cert| cert.status).collect();
+    assert_eq!(statuses, [CertificateStatus::Pass, CertificateStatus::Fail, CertificateStatus::Error, CertificateStatus::Error]);
+    assert!(summary.certificates[..2].iter().all(|cert| cert.reused && cert.proof_id.is_some()));
+    assert!(summary.certificates[2].error.as_deref().unwrap().contains("certificate_id"));
+    assert_eq!((summary.passed, summary.failed, summary.errors), (1, 1, 2));
+
+    let written: ShipmentSummary = serde_json::from_slice(&std::fs::read(&summary_path).unwrap()).unwrap();
+    assert!(written.finished_at.is_some());
+    assert_eq!(written.certificates.len(), 4);
+}
+
+#[test]
+fn manifest_lists_files_relative_to_itself() {
+    let dir = temp_dir();
+    std::fs::write(dir.join("shipment.csv"), "po_number, file\nPO-1, certs/a.json\nPO-2, /abs/b.json\n").unwrap();
+
+    let source = ShipmentSource::Manifest(dir.join("shipment.csv"));
+    assert_eq!(source.name(), "shipment");
+    assert_eq!(source.files().unwrap(), [dir.join("certs/a.json"), PathBuf::from("/abs/b.json")]);
+}
@@ -0,0 +1,127 @@
+//! Every store backend keeps records the same way: what is put comes back, listings page
+//! newest first, and deleting a proof takes its job with it.
+
+mod common;
+
+use material_pico_verify::store::{FsStore, Job, JobState, ProofQuery, ProofStore, SqliteStore};
+use material_pico_verify::types::{ProofRecord, ProvingMode};
+
+/// Proofs of three certificates from two manufacturers, generated on consecutive days
+fn records() -> Vec<ProofRecord> {
+    (1..=3)
+        .map(|day| {
+            let mut cert = common::certification(&format!("CERT-STORE-00{}", day));
+            if day == 3 {
+                cert.material.manufacturer = "Globex".to_string();
+            }
+            ProofRecord {
+                timestamp: format!("2024-01-0{}T00:00:00+00:00", day),
+                ..common::record(cert, vec![1, 1])
+            }
+        })
+        .collect()
+}
+
+fn round_trip(store: &dyn ProofStore) {
+    let record = records().remove(0);
+    assert!(store.get_proof(&record.id).unwrap().is_none());
+    store.put_proof(&record).unwrap();
+
+    let stored = store.get_proof(&record.id).unwrap().unwrap();
+    assert_eq!(serde_json::to_value(&stored).unwrap(), serde_json::to_value(&record).unwrap());
+
+    // Putting a record again replaces it
+    let replaced = ProofRecord { mode: ProvingMode::Full, ..record.clone() };
+    store.put_proof(&replaced).unwrap();
+    assert_eq!(store.get_proof(&record.id).unwrap().unwrap().mode, ProvingMode::Full);
+
+    store.put_job(&Job::new(&record.id, JobState::Completed, None)).unwrap();
+    assert_eq!(store.get_job(&record.id).unwrap().unwrap().state, JobState::Completed);
+}
+
+fn listing(store: &dyn ProofStore) {
+    let records = records();
+    for record in &records {
+        store.put_proof(record).unwrap();
+    }
+
+    let page = store.list_proofs(&ProofQuery { limit: 2, ..ProofQuery::default() }).unwrap();
+    assert_eq!(page.total, 3);
+    let ids: Vec<_> = page.items.iter().map(|item| item.id.clone()).collect();
+    assert_eq!(ids, [records[2].id.clone(), records[1].id.clone()]);
+
+    let page = store.list_proofs(&ProofQuery { limit: 2, offset: 2, ..ProofQuery::default() }).unwrap();
+    assert_eq!(page.total, 3);
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].id, records[0].id);
+    assert_eq!(page.items[0].certificate_id, "CERT-STORE-001");
+
+    let page = store.list_proofs(&ProofQuery { manufacturer: Some("Acme".to_string()), ..ProofQuery::default() }).unwrap();
+    assert_eq!(page.total, 2);
+    let page = store.list_proofs(&ProofQuery { from: Some("2024-01-02T00:00:00Z".parse().unwrap()), ..ProofQuery::default() }).unwrap();
+    assert_eq!(page.total, 2);
+
+    // Page sizes are clamped, so asking for none still returns one
+    let page = store.list_proofs(&ProofQuery { limit: 0, ..ProofQuery::default() }).unwrap();
+    assert_eq!(page.items.len(), 1);
+}
+
+fn deletion(store: &dyn ProofStore) {
+    let record = records().remove(0);
+    store.put_proof(&record).unwrap();
+    store.put_job(&Job::new(&record.id, JobState::Completed, None)).unwrap();
+
+    assert!(store.delete_proof(&record.id).unwrap());
+    assert!(store.get_proof(&record.id).unwrap().is_none());
+    assert!(store.get_job(&record.id).unwrap().is_none());
+    assert!(!store.delete_proof(&record.id).unwrap());
+}
+
+#[test]
+fn fs_store_keeps_records() {
+    round_trip(&FsStore::new(common::temp_dir()).unwrap());
+    listing(&FsStore::new(common::temp_dir()).unwrap());
+    deletion(&FsStore::new(common::temp_dir()).unwrap());
+}
+
+#[test]
+fn sqlite_store_keeps_records() {
+    let open = || SqliteStore::open(&common::temp_dir().join("proofs.db")).unwrap();
+    round_trip(&open());
+    listing(&open());
+    deletion(&open());
+}
+
+#[test]
+fn fs_store_leaves_nothing_behind_when_a_write_fails() {
+    let dir = common::temp_dir();
+    let store = FsStore::new(dir.clone()).unwrap();
+    let record = records().remove(0);
+
+    // A directory where the record belongs makes the final rename fail
+    let blocked = record.id.record_path(&dir);
+    std::fs::create_dir_all(blocked.join("occupied")).unwrap();
+    assert!(store.put_proof(&record).is_err());
+
+    let left: Vec<_> = std::fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(left, [blocked.file_name().unwrap()], "temporary files were left behind");
+    assert!(blocked.join("occupied").is_dir());
+
+    // Listings skip what they cannot read rather than failing
+    assert_eq!(store.list_proofs(&ProofQuery::default()).unwrap().total, 0);
+}
+
+#[test]
+fn fs_store_skips_malformed_records_in_listings() {
+    let dir = common::temp_dir();
+    let store = FsStore::new(dir.clone()).unwrap();
+    let records = records();
+    store.put_proof(&records[0]).unwrap();
+    std::fs::write(records[1].id.record_path(&dir), "{ partial").unwrap();
+
+    let page = store.list_proofs(&ProofQuery::default()).unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.items[0].id, records[0].id);
+}
@@ -0,0 +1,68 @@
+//! Organisations each see only their own proofs and are held to their own quota.
+
+mod common;
+
+use common::temp_dir;
+use material_pico_verify::store::{ProofQuery, StoreBackend, StoreConfig};
+use material_pico_verify::tenant::{Quota, Tenants};
+use material_pico_verify::types::{OrgId, ProofRecord};
+use std::collections::HashMap;
+
+fn org(name: &str) -> OrgId {
+    name.parse().unwrap()
+}
+
+fn sample_record() -> ProofRecord {
+    common::record(common::certification("CERT-TENANT-001"), vec![1])
+}
+
+#[test]
+fn proofs_are_only_visible_to_their_organisation() {
+    for backend in [StoreBackend::Fs, StoreBackend::Sqlite] {
+        let dir = temp_dir();
+        let tenants = Tenants::new(dir.clone(), StoreConfig { backend, ..Default::default() }, HashMap::new());
+        let acme = tenants.get(&org("acme")).unwrap();
+        let globex = tenants.get(&org("globex")).unwrap();
+
+        let record = sample_record();
+        acme.store.put_proof(&record).unwrap();
+
+        assert!(acme.store.get_proof(&record.id).unwrap().is_some());
+        assert!(globex.store.get_proof(&record.id).unwrap().is_none());
+        assert_eq!(globex.store.list_proofs(&ProofQuery::default()).unwrap().total, 0);
+        assert!(!globex.store.delete_proof(&record.id).unwrap());
+
+        // The default organisation keeps the original layout at the top of the output directory
+        assert_eq!(tenants.get(&OrgId::default()).unwrap().work_dir, dir);
+        assert_eq!(acme.work_dir, dir.join("orgs").join("acme"));
+    }
+}
+
+#[test]
+fn quotas_are_enforced_per_organisation() {
+    let quotas = HashMap::from([(org("acme"), Quota { proofs_per_day: Some(3), max_batch_size: Some(2) })]);
+    let tenants = Tenants::new(temp_dir(), StoreConfig::default(), quotas);
+    let acme = tenants.get(&org("acme")).unwrap();
+
+    assert!(acme.reserve(3).is_err(), "batch larger than allowed");
+    acme.reserve(2).unwrap();
+    acme.reserve(1).unwrap();
+    assert!(acme.reserve(1).is_err(), "daily quota used up");
+
+    // Proofs that failed do not count
+    acme.refund(1);
+    acme.reserve(1).unwrap();
+    assert!(acme.reserve(1).is_err());
+
+    // Organisations without a quota are not limited
+    let globex = tenants.get(&org("globex")).unwrap();
+    globex.reserve(10).unwrap();
+}
+
+#[test]
+fn org_ids_are_safe_path_components() {
+    assert!("acme-steel-2".parse::<OrgId>().is_ok());
+    for invalid in ["", "../etc", "Acme", "-acme", "acme/x", &"a".repeat(64)] {
+        assert!(invalid.parse::<OrgId>().is_err(), "{:?} accepted", invalid);
+    }
+}
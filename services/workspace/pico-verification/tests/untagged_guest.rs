@@ -0,0 +1,33 @@
+//! Records from before program hashes were recorded are read with the release the configuration
+//! names for them. The registry is set once per process, so this lives apart from tests/guest.rs.
+
+mod common;
+
+use material_pico_verify::circuit;
+use material_pico_verify::registry::{self, GuestVersion, OutputLayout};
+use material_pico_verify::types::ProofRecord;
+
+const ORIGINAL_RELEASE: &str = "0000000000000000000000000000000000000000000000000000000000000003";
+
+#[test]
+fn untagged_records_are_read_with_the_named_release() {
+    registry::use_guest_versions(vec![GuestVersion {
+        program_hash: ORIGINAL_RELEASE.to_string(),
+        version: "0.0.1".to_string(),
+        output_layout: OutputLayout::ComplianceV0,
+        verifying_key: None,
+        released: None,
+        deprecated: true,
+        untagged: true,
+    }]);
+
+    let record = ProofRecord { program_hash: None, ..common::record(common::certification("CERT-UNTAGGED-001"), vec![0, 1]) };
+    let result = circuit::verify_proof(&record, &record.certification, &common::temp_dir()).unwrap();
+    let program = result.zkp_info.program.unwrap();
+    assert_eq!(program.program_hash, ORIGINAL_RELEASE);
+    assert!(program.deprecated);
+
+    // The original guest committed verdicts in hash map order, so only the overall one is read
+    assert_eq!(result.overall_compliance, "FAIL");
+    assert!(result.properties.is_empty());
+}
@@ -0,0 +1,80 @@
+//! Certifications are checked field by field before they are proven.
+
+mod common;
+
+use material_pico_verify::types::MaterialCertification;
+use material_pico_verify::validation;
+
+fn certification(public_properties: serde_json::Value) -> MaterialCertification {
+    let mut cert = common::certification("CERT-VALID-001");
+    cert.properties_tested.public_properties = common::properties(public_properties);
+    cert
+}
+
+#[test]
+fn problems_are_reported_per_field() {
+    let cert = certification(serde_json::json!({
+        "yield_strength": { "value": 250, "unit": "MPa", "threshold": { "min": 220, "max": 300 }, "passed": true }
+    }));
+    assert!(validation::validate(&cert).is_empty());
+
+    let mut cert = certification(serde_json::json!({
+        "yield_strength": { "value": 250, "unit": "MPa", "threshold": { "min": 300, "max": 220 }, "passed": true },
+        "finish": { "value": "smooth", "unit": null, "threshold": { "min": 0, "max": 1 }, "passed": true }
+    }));
+    cert.certificate_id = " ".to_string();
+    let fields: Vec<_> = validation::validate(&cert).into_iter().map(|error| error.field).collect();
+    assert_eq!(fields, [
+        "certificate_id",
+        "properties_tested.public_properties.finish.value",
+        "properties_tested.public_properties.yield_strength.threshold",
+    ]);
+}
+
+#[test]
+fn contradictions_between_fields_are_reported() {
+    let mut cert = certification(serde_json::json!({
+        "yield_strength": { "value": 320, "unit": "MPa", "threshold": { "min": 220, "max": 300 }, "passed": true },
+        "elongation": { "value": 20, "unit": "%", "threshold": null, "passed": false }
+    }));
+    cert.properties_tested.private_properties.insert(
+        "yield_strength".to_string(),
+        cert.properties_tested.public_properties["yield_strength"].clone(),
+    );
+    cert.properties_tested.private_properties.get_mut("yield_strength").unwrap().passed = false;
+    cert.batch.production_date = "2024-03-01T08:00:00Z".to_string();
+    cert.batch.expiration_date = Some("01/02/2025".to_string());
+    cert.batch.quantity = 0;
+    cert.verified_by.test_date = "2024-02-28".to_string();
+
+    let fields: Vec<_> = validation::validate(&cert).into_iter().map(|error| error.field).collect();
+    assert_eq!(fields, [
+        "batch.expiration_date",
+        "batch.quantity",
+        "properties_tested.public_properties.yield_strength.passed",
+        "properties_tested.private_properties.yield_strength",
+        "verified_by.test_date",
+    ]);
+}
+
+#[test]
+fn unreadable_documents_are_one_error() {
+    let report = validation::validate_json("request".to_string(), br#"{"certificate_id": "CERT-1""#);
+    assert!(!report.valid);
+    assert_eq!(report.errors.len(), 1);
+    assert!(report.errors[0].field.is_empty());
+}
+
+#[test]
+fn certificate_ids_must_be_unique_within_a_batch() {
+    let cert = certification(serde_json::json!({}));
+    let mut other = cert.clone();
+    other.certificate_id = "CERT-VALID-002".to_string();
+    assert!(validation::duplicate_certificates(&[cert.clone(), other.clone()]).is_empty());
+
+    let fields: Vec<_> = validation::duplicate_certificates(&[cert.clone(), other.clone(), cert.clone(), other])
+        .into_iter()
+        .map(|error| error.field)
+        .collect();
+    assert_eq!(fields, ["certifications[2].certificate_id", "certifications[3].certificate_id"]);
+}
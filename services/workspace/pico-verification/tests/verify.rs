@@ -0,0 +1,32 @@
+//! A proof covers the certification its content-addressed id was derived from, and no other.
+
+mod common;
+
+use material_pico_verify::circuit;
+use material_pico_verify::types::{ProofRecord, ProvingMode};
+
+fn proven(mode: ProvingMode) -> ProofRecord {
+    let certification = common::certification("CERT-COVER-001");
+    let program_hash = circuit::guest_program_hash();
+    ProofRecord {
+        id: circuit::content_hash(&certification, program_hash, mode).unwrap().parse().unwrap(),
+        mode,
+        program_hash: Some(program_hash.to_string()),
+        ..common::record(certification, vec![1, 1])
+    }
+}
+
+#[test]
+fn proofs_cover_only_the_certification_they_were_made_for() {
+    let record = proven(ProvingMode::Full);
+    assert!(circuit::covers(&record, &record.certification).unwrap());
+
+    let mut other = record.certification.clone();
+    other.properties_tested.public_properties.get_mut("yield_strength").unwrap().value = serde_json::json!(290);
+    assert!(!circuit::covers(&record, &other).unwrap());
+
+    // Swapping the certification inside the record does not make it cover the new one
+    let swapped = ProofRecord { certification: other.clone(), ..record };
+    assert!(!circuit::covers(&swapped, &other).unwrap());
+    assert!(!circuit::covers(&swapped, &swapped.certification).unwrap());
+}
@@ -0,0 +1,26 @@
+#![no_main]
+
+use material_verification_lib::batch::{certificate_commitment, merkle_root, BatchCertificate, BatchOutput};
+use pico_sdk::entrypoint;
+use pico_sdk::io::{read_as, commit};
+
+entrypoint!(main);
+
+pub fn main() {
+    // Certificates arrive in batch order; their positions are the Merkle leaf indices
+    let certificates: Vec<BatchCertificate> = read_as();
+    
+    let mut commitments = Vec::with_capacity(certificates.len());
+    let mut verdicts = Vec::with_capacity(certificates.len());
+    
+    for certificate in &certificates {
+        commitments.push(certificate_commitment(certificate));
+        verdicts.push(certificate.is_compliant());
+    }
+    
+    // Commit the root of all certificate commitments plus one verdict per certificate
+    commit(&BatchOutput {
+        merkle_root: merkle_root(&commitments),
+        verdicts,
+    });
+}
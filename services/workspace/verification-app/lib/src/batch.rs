@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// One numeric property of a certificate as the guests check it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheckedProperty {
+    pub name: String,
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// A certificate reduced to what the batch guest needs to check and commit to
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchCertificate {
+    pub certificate_id: String,
+    /// Random blinding so commitments don't leak low-entropy private values
+    pub salt: [u8; 32],
+    pub properties: Vec<CheckedProperty>,
+}
+
+impl BatchCertificate {
+    /// A certificate passes when every property is within its threshold
+    pub fn is_compliant(&self) -> bool {
+        self.properties.iter().all(|p| crate::is_within_range(p.value, p.min, p.max))
+    }
+}
+
+/// Values committed by the batch guest
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatchOutput {
+    pub merkle_root: [u8; 32],
+    pub verdicts: Vec<bool>,
+}
+
+/// Hash binding a certificate's identifier, salt and checked properties
+pub fn certificate_commitment(cert: &BatchCertificate) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hash_str(&mut hasher, &cert.certificate_id);
+    hasher.update(cert.salt);
+    hasher.update((cert.properties.len() as u64).to_le_bytes());
+    for property in &cert.properties {
+        hash_str(&mut hasher, &property.name);
+        hasher.update(property.value.to_le_bytes());
+        hasher.update(property.min.to_le_bytes());
+        hasher.update(property.max.to_le_bytes());
+    }
+    hasher.finalize().into()
+}
+
+fn hash_str(hasher: &mut Sha256, value: &str) {
+    hasher.update((value.len() as u64).to_le_bytes());
+    hasher.update(value.as_bytes());
+}
+
+// Leaves and inner nodes are domain-separated so a node can never pass as a leaf
+fn hash_leaf(commitment: &[u8; 32]) -> [u8; 32] {
+    Sha256::new().chain_update([0u8]).chain_update(commitment).finalize().into()
+}
+
+fn hash_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    Sha256::new().chain_update([1u8]).chain_update(left).chain_update(right).finalize().into()
+}
+
+/// Hash each level of the tree; an unpaired last node is promoted unchanged
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_node(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Merkle root over certificate commitments, in batch order
+pub fn merkle_root(commitments: &[[u8; 32]]) -> [u8; 32] {
+    if commitments.is_empty() {
+        return [0u8; 32];
+    }
+
+    let mut level: Vec<[u8; 32]> = commitments.iter().map(hash_leaf).collect();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// Sibling hashes from a leaf up to the root; `None` marks a level where the node was promoted
+pub fn merkle_path(commitments: &[[u8; 32]], index: usize) -> Vec<Option<[u8; 32]>> {
+    let mut path = Vec::new();
+    let mut level: Vec<[u8; 32]> = commitments.iter().map(hash_leaf).collect();
+    let mut position = index;
+
+    while level.len() > 1 {
+        path.push(level.get(position ^ 1).copied());
+        level = next_level(&level);
+        position /= 2;
+    }
+    path
+}
+
+/// Check that a commitment sits at `index` under `root`
+pub fn verify_inclusion(commitment: &[u8; 32], index: usize, path: &[Option<[u8; 32]>], root: &[u8; 32]) -> bool {
+    let mut hash = hash_leaf(commitment);
+    let mut position = index;
+
+    for sibling in path {
+        if let Some(sibling) = sibling {
+            // A bit test rather than `is_multiple_of`, which the guest toolchain predates
+            hash = if position & 1 == 0 { hash_node(&hash, sibling) } else { hash_node(sibling, &hash) };
+        }
+        position /= 2;
+    }
+    position == 0 && &hash == root
+}
@@ -1,58 +1,27 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct PropertyValue {
-    pub value: f64,
-    pub unit: Option<String>,
-    pub threshold: Option<Threshold>,
-    pub passed: bool,
-}
+pub mod batch;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Threshold {
-    pub min: f64,
-    pub max: f64,
-}
+use batch::CheckedProperty;
 
+/// What the host writes to the certification guest's stdin.
+///
+/// Properties are public then private, each sorted by name. The guest commits the overall
+/// verdict followed by one verdict per property in this order, which is how the host knows
+/// which verdict belongs to which property.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct VerificationResult {
-    pub material_id: String,
-    pub material_type: String,
-    pub standard: String,
-    pub timestamp: String,
-    pub verification_id: String,
-    pub overall_compliance: String,
-    pub property_results: Vec<PropertyResult>,
-    pub zkp_info: Option<ZkpInfo>,
+pub struct ComplianceInput {
+    pub properties: Vec<CheckedProperty>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct PropertyResult {
-    pub property: String,
-    pub value: Option<f64>,
-    pub threshold: Option<Threshold>,
-    pub compliant: bool,
-    pub is_private: bool,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ZkpInfo {
-    pub implemented: bool,
-    pub proof_type: String,
-    pub circuit: String,
-    pub public_values: String,
-    pub verified: bool,
+impl ComplianceInput {
+    /// Whether each property is within its threshold, in input order
+    pub fn verdicts(&self) -> Vec<bool> {
+        self.properties.iter().map(|p| is_within_range(p.value, p.min, p.max)).collect()
+    }
 }
 
 // Helper functions for common operations
 pub fn is_within_range(value: f64, min: f64, max: f64) -> bool {
     value >= min && value <= max
 }
-
-pub fn format_verification_id(material_type: &str, timestamp: u64) -> String {
-    format!("VER-{}-{}-{:04}", 
-        material_type.chars().take(3).collect::<String>().to_uppercase(),
-        timestamp,
-        rand::random::<u16>() % 10000
-    )
-}
\ No newline at end of file